@@ -0,0 +1,70 @@
+// A Go engine speaking GTP on stdin and stdout, for use with graphical front
+// ends such as GoGui or Sabaki, or tournament tools like gogui-twogtp.
+//
+// Moves come from Monte Carlo tree search, or uniformly at random with
+// --random.
+
+extern crate go;
+
+use std::env;
+use std::io::{self, Write};
+use std::process;
+use std::time::Duration;
+
+use go::game::Game;
+use go::gtp::Engine;
+use go::mcts::Mcts;
+use go::player::{Player, RandomPlayer};
+use go::policy::Weights;
+use go::rules::Ruleset;
+
+fn usage() -> ! {
+    let _ = writeln!(io::stderr(),
+                     "usage: gtp [--rules RULES] [--random] [--playouts N] [--time SECONDS] [--threads N] [--patterns FILE]");
+    process::exit(1)
+}
+
+fn main() {
+    let mut rules = Ruleset::default();
+    let mut random = false;
+    let mut playouts = Some(1000);
+    let mut time = None;
+    let mut threads = 1;
+    let mut patterns = None;
+
+    let mut args = env::args().skip(1);
+    while let Some(a) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match &a[..] {
+            "--rules" => rules = value().parse().unwrap_or_else(|_| usage()),
+            "--random" => random = true,
+            "--playouts" => playouts = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--time" => {
+                let secs: f64 = value().parse().unwrap_or_else(|_| usage());
+                time = Some(Duration::from_millis((secs * 1000.0) as u64));
+                if playouts == Some(1000) { playouts = None }
+            },
+            "--threads" => threads = value().parse().unwrap_or_else(|_| usage()),
+            "--patterns" => {
+                let path = value();
+                patterns = Some(Weights::load(&path).unwrap_or_else(|e| panic!("can't load {}: {}", path, e)));
+            },
+            _ => usage(),
+        }
+    }
+
+    let player: Box<Player> = if random {
+        Box::new(RandomPlayer::new())
+    } else {
+        let mut m = Mcts::new(Game::new(19, rules));
+        m.set_playouts(playouts);
+        m.set_time(time);
+        m.set_threads(threads);
+        m.set_policy(patterns);
+        Box::new(m)
+    };
+
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    Engine::new(rules, player).run(stdin.lock(), stdout.lock()).expect("i/o error");
+}
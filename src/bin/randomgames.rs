@@ -0,0 +1,71 @@
+extern crate go;
+
+use std::env;
+
+use go::board::Board;
+use go::game::{Game, Move};
+use go::player::{Player, RandomPlayer};
+use go::rules::Ruleset;
+use go::stone::Stone;
+
+// Check the board is consistent after every move, panicking if not
+fn check(game: &Game, before: &Board) {
+    let board = game.board();
+
+    // The incrementally updated hash matches one computed from scratch
+    let mut fresh = Board::new_with_size(board.size());
+    for l in board.locations() {
+        if let Some(s) = board.get(l) { let _ = fresh.add(l, s); }
+    }
+    assert_eq!(board.zobrist(), fresh.zobrist(), "zobrist hash mismatch\n{}", board);
+
+    for &colour in &[Stone::Black, Stone::White] {
+        for g in board.groups::<Vec<_>>(colour) {
+            assert!(board.liberties::<Vec<_>>(&g).len() > 0, "group without liberties\n{}", board);
+        }
+
+        let played = game.history().iter().filter(|t| t.colour() == colour && t.mv() != Move::Pass).count();
+        let on_board = board.locations().filter(|l| board.get(l) == Some(colour)).count();
+        assert_eq!(played, on_board + game.captures(!colour), "lost a {:?} stone\n{}", colour, board);
+    }
+
+    let last = game.history().last().expect("no moves");
+    assert_eq!(last.before().zobrist(), before.zobrist(), "history doesn't match\n{}", board);
+}
+
+fn main() {
+    let args: Vec<_> = env::args().collect();
+    let games: usize = args.get(1).map_or(1000, |s| s.parse().expect("number of games"));
+    let size: usize = args.get(2).map_or(9, |s| s.parse().expect("board size"));
+    let rules: Ruleset = args.get(3).map_or(Ruleset::default(), |s| s.parse().expect("rules"));
+
+    let mut player = match args.get(4) {
+        Some(s) => RandomPlayer::with_seed(s.parse().expect("seed")),
+        None => RandomPlayer::new(),
+    };
+
+    let mut wins = [0, 0];
+    let mut moves = 0;
+
+    for _ in 0..games {
+        let mut game = Game::new(size, rules);
+
+        while !game.over() && game.history().len() < size * size * 4 {
+            let colour = game.tomove();
+            let before = game.board().clone();
+            let mv = player.genmove(&game, colour);
+            game.play(colour, mv).expect("illegal move");
+            check(&game, &before);
+        }
+        moves += game.history().len();
+
+        match game.score().winner() {
+            Some(Stone::Black) => wins[0] += 1,
+            Some(Stone::White) => wins[1] += 1,
+            None => (),
+        }
+    }
+
+    println!("{} games on {}x{} ({} rules): Black won {}, White won {}, {:.1} moves per game",
+             games, size, size, rules, wins[0], wins[1], moves as f64 / games as f64);
+}
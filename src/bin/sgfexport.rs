@@ -0,0 +1,229 @@
+// Export training data for neural networks from SGF collections.
+//
+// Every position in the main line of each game becomes one example: the
+// feature planes for the player to move, the move they played and whether
+// they went on to win. The examples go into three NumPy files:
+//
+//     <prefix>.features.npy   float32 (N, PLANES, size, size)
+//     <prefix>.policy.npy     int32 (N,), row * size + col, or size * size for a pass
+//     <prefix>.value.npy      float32 (N,), 1 for a win, -1 for a loss, 0 for a draw
+//
+// Games on other board sizes, without a known result, with an illegal move or
+// with setup stones which can't be read are skipped.
+
+extern crate go;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, BufWriter, Read, Seek, SeekFrom, Write};
+use std::process;
+
+use go::features::{self, PLANES};
+use go::game::{self, Game, Move};
+use go::location::Location;
+use go::sgf::{self, Node};
+use go::stone::Stone;
+
+// Space reserved for the header, which can only be written once the number
+// of examples is known
+const HEADER: usize = 128;
+
+// A NumPy array file, written one row at a time
+struct Npy {
+    file: BufWriter<File>,
+    descr: &'static str,
+    dims: Vec<usize>,
+    rows: usize,
+}
+
+impl Npy {
+    fn create(path: &str, descr: &'static str, dims: &[usize]) -> io::Result<Npy> {
+        let mut file = BufWriter::new(try!(File::create(path)));
+        try!(file.write_all(&[b' '; HEADER]));
+        Ok(Npy { file: file, descr: descr, dims: dims.to_vec(), rows: 0 })
+    }
+
+    fn write_u32(&mut self, v: u32) -> io::Result<()> {
+        self.file.write_all(&[v as u8, (v >> 8) as u8, (v >> 16) as u8, (v >> 24) as u8])
+    }
+
+    fn write_f32(&mut self, v: f32) -> io::Result<()> { self.write_u32(v.to_bits()) }
+
+    fn finish(mut self) -> io::Result<()> {
+        let mut shape = format!("({},", self.rows);
+        for d in &self.dims { shape.push_str(&format!(" {},", d)) }
+        if !self.dims.is_empty() { shape.pop(); }
+        shape.push(')');
+
+        let mut dict = format!("{{'descr': '{}', 'fortran_order': False, 'shape': {}, }}", self.descr, shape);
+        while dict.len() < HEADER - 11 { dict.push(' ') }
+        dict.push('\n');
+
+        let mut header = b"\x93NUMPY\x01\x00".to_vec();
+        header.push((dict.len() & 0xff) as u8);
+        header.push((dict.len() >> 8) as u8);
+        header.extend(dict.bytes());
+        assert_eq!(header.len(), HEADER);
+
+        try!(self.file.seek(SeekFrom::Start(0)));
+        try!(self.file.write_all(&header));
+        self.file.flush()
+    }
+}
+
+// Reasons for skipping a game
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Skip {
+    Size,
+    Result,
+    Illegal,
+    Info,
+    Setup,
+}
+
+// One example: the planes, the index of the move and the result for the
+// player to move
+struct Example {
+    planes: Vec<f32>,
+    mv: usize,
+    value: f32,
+}
+
+// Winner from the RE property; None for a draw. Unknown or missing results,
+// and void games, give an error.
+fn winner(root: &Node) -> Result<Option<Stone>, Skip> {
+    let re = match root.prop("RE").and_then(|p| p.value().ok()) {
+        Some(v) => v.simpletext().map(|s| { let s: String = s.into(); s.trim().to_lowercase() }),
+        None => None,
+    };
+
+    match re {
+        Some(ref r) if r.starts_with("b+") => Ok(Some(Stone::Black)),
+        Some(ref r) if r.starts_with("w+") => Ok(Some(Stone::White)),
+        Some(ref r) if r == "0" || r == "draw" || r == "jigo" => Ok(None),
+        _ => Err(Skip::Result),
+    }
+}
+
+// Replay the main line of the game
+fn replay(root: &Node, size: usize) -> Result<Vec<Example>, Skip> {
+    let mut game = try!(Game::from_sgf(root).map_err(|_| Skip::Info));
+    if game.size() != size { return Err(Skip::Size) }
+    let winner = try!(winner(root));
+
+    let mut examples = Vec::new();
+    let mut node = root;
+    loop {
+        if let Some(m) = game::sgf_move(node, size) {
+            let (colour, mv) = try!(m.map_err(|_| Skip::Illegal));
+            game.set_tomove(colour);
+            examples.push(Example {
+                planes: features::encode(&game),
+                mv: match mv { Move::Play(l) => l.row() * size + l.col(), Move::Pass => size * size },
+                value: match winner { Some(w) if w == colour => 1.0, Some(_) => -1.0, None => 0.0 },
+            });
+            try!(game.play(colour, mv).map_err(|_| Skip::Illegal));
+        }
+
+        if node.len() == 0 { break }
+        node = &node[0];
+        try!(game.setup_sgf(node).map_err(|_| Skip::Setup));
+    }
+
+    Ok(examples)
+}
+
+fn usage() -> ! {
+    let _ = writeln!(io::stderr(), "usage: sgfexport [--symmetry] [--size N] <output prefix> <sgf file>...");
+    process::exit(1)
+}
+
+fn main() {
+    let mut symmetry = false;
+    let mut size = 19;
+    let mut args = env::args().skip(1).peekable();
+
+    while let Some(a) = args.peek().cloned() {
+        match &a[..] {
+            "--symmetry" => symmetry = true,
+            "--size" => {
+                let _ = args.next();
+                size = args.peek().and_then(|s| s.parse().ok()).unwrap_or_else(|| usage());
+            },
+            _ => break,
+        }
+        let _ = args.next();
+    }
+
+    let prefix = args.next().unwrap_or_else(|| usage());
+    let files: Vec<_> = args.collect();
+    if files.is_empty() { usage() }
+
+    let create = |name, descr, dims: &[usize]| {
+        let path = format!("{}.{}.npy", prefix, name);
+        Npy::create(&path, descr, dims).unwrap_or_else(|e| panic!("can't create {}: {}", path, e))
+    };
+    let mut planes = create("features", "<f4", &[PLANES, size, size]);
+    let mut policy = create("policy", "<i4", &[]);
+    let mut value = create("value", "<f4", &[]);
+
+    let mut games = 0;
+    let mut skipped = Vec::new();
+    let mut unreadable = 0;
+
+    for f in &files {
+        let mut txt = Vec::new();
+        let coll = File::open(f).and_then(|mut file| file.read_to_end(&mut txt))
+            .map_err(|e| e.to_string())
+            .and_then(|_| sgf::parser(&txt).map_err(|e| format!("{:?}", e)));
+        let coll = match coll {
+            Ok(c) => c,
+            Err(e) => {
+                let _ = writeln!(io::stderr(), "{}: {}", f, e);
+                unreadable += 1;
+                continue
+            },
+        };
+
+        for root in &coll {
+            let examples = match replay(root, size) {
+                Ok(e) => e,
+                Err(s) => { skipped.push(s); continue },
+            };
+            games += 1;
+
+            for e in examples {
+                for t in 0..(if symmetry { 8 } else { 1 }) {
+                    let mv = if e.mv < size * size {
+                        let l = features::transform_loc(Location::new(e.mv % size, e.mv / size), size, t);
+                        l.row() * size + l.col()
+                    } else {
+                        e.mv
+                    };
+
+                    for &p in &features::transform(&e.planes, size, t) {
+                        planes.write_f32(p).expect("write features");
+                    }
+                    policy.write_u32(mv as u32).expect("write policy");
+                    value.write_f32(e.value).expect("write value");
+                    planes.rows += 1;
+                    policy.rows += 1;
+                    value.rows += 1;
+                }
+            }
+        }
+    }
+
+    let examples = planes.rows;
+    for npy in vec![planes, policy, value] {
+        npy.finish().expect("finish output");
+    }
+
+    let count = |s| skipped.iter().filter(|&&k| k == s).count();
+    println!("{} games, {} examples from {} files", games, examples, files.len());
+    println!("skipped {} games: {} not {}x{}, {} without a result, {} with illegal moves, {} with bad game info, \
+              {} with bad setup",
+             skipped.len(), count(Skip::Size), size, size, count(Skip::Result), count(Skip::Illegal), count(Skip::Info),
+             count(Skip::Setup));
+    if unreadable > 0 { println!("{} files couldn't be read", unreadable) }
+}
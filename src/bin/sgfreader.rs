@@ -5,10 +5,10 @@ use std::io::Read;
 use std::env;
 
 use go::sgf;
-use go::sgf::property::{Value};
-use go::board::Board;
+use go::game::{self, Game};
+use go::safety;
+use go::shape;
 use go::stone::Stone;
-use go::location::Location;
 
 fn main() {
     let args: Vec<_> = env::args().collect();
@@ -18,7 +18,9 @@ fn main() {
 
     let sgfcoll = sgf::parser(&txt[..]).expect("parse");
     let mut node = &sgfcoll[0];
-    let mut sz = 19;
+    let mut game = Game::from_sgf(node).expect("game info");
+
+    println!("rules {:?} komi {} handicap {}", game.rules(), game.komi(), game.handicap());
 
     while !node.movenode() {
         println!("root {:?} setup {:?} move {:?}", node.rootnode(), node.setupnode(), node.movenode());
@@ -27,38 +29,41 @@ fn main() {
             println!("{}: {:?}", v.id(), v.values())
         }
 
-        sz = match node.prop("SZ").and_then(|p| p.value().ok()) {
-            Some(Value::Number(ref n)) => n.into(),
-            _ => 19,
-        };
-
         if node.len() == 0 { break }
         node = &node[0];
+        game.setup_sgf(node).expect("setup");
     }
 
-    let mut board = Board::new_with_size(sz);
-
     let mut movenum = 1;
     while node.movenode() {
-        for &(p, c) in &[("B", Stone::Black), ("W", Stone::White)] {
-            let p = node.prop(p).and_then(|p| p.value().ok());
-            let m: Option<Location> = p.and_then(|v| v.gomove().map(Into::into));
-            if let Some(loc) = m {
-                let loc = Location::new(loc.col(), sz - 1 - loc.row());
-                println!("Move {}: {:?} {}", movenum, c, loc);
-                movenum += 1;
-                if !board.play(loc, c) {
-                    println!("bad play: {} {:?}", loc, c)
-                } else {
-                    println!("{}", board);
+        if let Some(m) = game::sgf_move(node, game.size()) {
+            let (c, mv) = m.expect("bad move");
+            println!("Move {}: {:?} {}", movenum, c, mv);
+            movenum += 1;
+            if let Err(e) = game.play(c, mv) {
+                println!("bad play: {} {:?}: {:?}", mv, c, e)
+            } else {
+                println!("{}", game.board());
+
+                if let game::Move::Play(loc) = mv {
+                    for a in shape::shapes(game.board(), loc) {
+                        println!("shape {}", a);
+                    }
+                }
+                println!("captured {} prisoners: Black {} White {}",
+                         game.history().last().map_or(0, |t| t.captures()),
+                         game.captures(Stone::Black), game.captures(Stone::White));
 
-                    for g in board.groups::<Vec<_>>(c) {
-                        print!("{:?} group: {} liberties: [", c, g);
-                        for l in board.liberties::<Vec<_>>(&g) {
-                            print!(" {}", l)
-                        }
-                        println!(" ]");
+                for g in game.board().groups::<Vec<_>>(c) {
+                    print!("{:?} group: {} liberties: [", c, g);
+                    for l in game.board().liberties::<Vec<_>>(&g) {
+                        print!(" {}", l)
                     }
+                    println!(" ]");
+                }
+
+                for s in safety::classify(game.board()).into_iter().filter(|s| s.at_risk()) {
+                    println!("{:?} group at risk ({:?}): {}", s.group().colour(), s.safety(), s.group());
                 }
             }
         }
@@ -67,5 +72,5 @@ fn main() {
         node = &node[0];
     }
 
-    println!("Board:\n{}", board);
+    println!("Board:\n{}", game.board());
 }
@@ -0,0 +1,94 @@
+// Play a match between two GTP engines.
+//
+// The engines alternate colours from game to game, starting with the first
+// as Black; see `go::gtp::arena` for how games are refereed. Each game is
+// saved as <prefix>-<n>.sgf.
+
+extern crate go;
+
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::process::{self, Command};
+use std::time::Duration;
+
+use go::gtp::Controller;
+use go::gtp::arena::{self, Arena, End, Entrant, Settings};
+use go::sgf::Writer;
+
+fn usage() -> ! {
+    let _ = writeln!(io::stderr(), "usage: twogtp [--games N] [--size N] [--komi K] [--rules RULES] [--max-moves N] \
+                                    [--timeout SECONDS] [--sgf PREFIX] <engine command> <engine command>");
+    process::exit(1)
+}
+
+fn start(cmdline: &str, timeout: Option<Duration>) -> Entrant {
+    let mut words = cmdline.split_whitespace();
+    let program = words.next().unwrap_or_else(|| usage());
+    let mut ctl = Controller::spawn(Command::new(program).args(words))
+        .unwrap_or_else(|e| panic!("can't start {}: {}", cmdline, e));
+    ctl.set_timeout(timeout);
+
+    Entrant::new(ctl).unwrap_or_else(|e| panic!("{} didn't give its name: {}", cmdline, e))
+}
+
+// Percentage of `points` in `games`, with its 95% confidence interval
+fn rate(points: f32, games: usize) -> String {
+    if games == 0 { return "-".to_string() }
+    let (lo, hi) = arena::wilson(points, games);
+    format!("{:.1}% [{:.1}%, {:.1}%]", 100.0 * points / games as f32, 100.0 * lo, 100.0 * hi)
+}
+
+fn main() {
+    let mut games = 10;
+    let mut settings = Settings::new(19);
+    let mut max_moves = None;
+    let mut timeout = None;
+    let mut prefix = None;
+    let mut engines = Vec::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(a) = args.next() {
+        let mut value = || args.next().unwrap_or_else(|| usage());
+        match &a[..] {
+            "--games" => games = value().parse().unwrap_or_else(|_| usage()),
+            "--size" => settings.size = value().parse().unwrap_or_else(|_| usage()),
+            "--komi" => settings.komi = value().parse().unwrap_or_else(|_| usage()),
+            "--rules" => settings.rules = value().parse().unwrap_or_else(|_| usage()),
+            "--max-moves" => max_moves = Some(value().parse().unwrap_or_else(|_| usage())),
+            "--timeout" => {
+                let secs: f64 = value().parse().unwrap_or_else(|_| usage());
+                timeout = Some(Duration::from_millis((secs * 1000.0) as u64));
+            },
+            "--sgf" => prefix = Some(value()),
+            _ if a.starts_with("--") => usage(),
+            _ => engines.push(a.clone()),
+        }
+    }
+    if engines.len() != 2 { usage() }
+    settings.max_moves = max_moves.unwrap_or(3 * settings.size * settings.size);
+
+    let mut arena = Arena::new(start(&engines[0], timeout), start(&engines[1], timeout), settings);
+
+    for n in 1..games + 1 {
+        let g = arena.play();
+
+        let why = match *g.end() { End::Forfeit(c, ref why) => format!(" ({} forfeits: {})", c, why), _ => String::new() };
+        println!("game {}: {} (B) vs {} (W): {} after {} moves{}", n, g.black(), g.white(),
+                 g.result(), g.game().history().len(), why);
+
+        if let Some(ref p) = prefix {
+            let path = format!("{}-{}.sgf", p, n);
+            File::create(&path).and_then(|mut f| Writer::new().write(&mut f, &[g.to_sgf()]))
+                .unwrap_or_else(|e| panic!("can't write {}: {}", path, e));
+        }
+    }
+
+    arena.quit();
+
+    println!("{} games, {} drawn", arena.games(), arena.draws());
+    for e in arena.entrants() {
+        println!("{}: {} wins, {} (as Black {})", e.name(), e.points(), rate(e.points(), arena.games()),
+                 rate(e.black_points(), e.black_games()));
+    }
+}
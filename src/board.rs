@@ -1,13 +1,12 @@
-use std::collections::hash_map::{HashMap};
 use std::iter::FromIterator;
 use std::cmp::max;
 use std::str::FromStr;
 use std::fmt::{self, Display};
 
-use bit_set::bitidx::BitSet;
+use bit_set::bitidx::{self, BitSet};
 
 use stone::Stone;
-use group::{Group, GroupIterator};
+use group::Group;
 use location::{Location, AllLocations};
 use one::One;
 
@@ -19,7 +18,26 @@ pub struct Point(Location, Option<Stone>);
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Board {
     size: usize,
-    points: HashMap<Location, Stone>,
+    // Indexed by row * size + col
+    points: Vec<Option<Stone>>,
+    zobrist: u64,
+    captures: [usize; 2],
+}
+
+fn idx(s: Stone) -> usize {
+    match s { Stone::Black => 0, Stone::White => 1 }
+}
+
+// Zobrist key for a stone at a location. Rather than keep a table of random
+// numbers, each key is derived by running the (location, colour) index through
+// a splitmix64 finaliser, which is good enough to make collisions vanishingly
+// unlikely.
+fn zobrist(loc: &Location, s: Stone) -> u64 {
+    let idx = (loc.col() * 100 + loc.row()) * 2 + idx(s);
+    let mut z = (idx as u64 + 1).wrapping_mul(0x9e3779b97f4a7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
 }
 
 impl Board {
@@ -27,12 +45,21 @@ impl Board {
     pub fn new_with_size(size: usize) -> Board {
         Board {
             size: size,
-            points: HashMap::new(),
+            points: vec![None; size * size],
+            zobrist: 0,
+            captures: [0, 0],
         }
     }
 
     pub fn size(&self) -> usize { self.size }
 
+    /// Hash of the stones on the board, suitable for detecting repeated positions
+    pub fn zobrist(&self) -> u64 { self.zobrist }
+
+    /// Number of stones `colour` has captured (including any opposing stones
+    /// which committed suicide)
+    pub fn captures(&self, colour: Stone) -> usize { self.captures[idx(colour)] }
+
     pub fn validloc<L>(&self, loc: L) -> bool
         where L: AsRef<Location>
     {
@@ -43,7 +70,9 @@ impl Board {
     pub fn get<L>(&self, loc: L) -> Option<Stone>
         where L: AsRef<Location>
     {
-        self.points.get(loc.as_ref()).map(|s| *s)
+        let loc = loc.as_ref();
+        if !self.validloc(loc) { return None }
+        self.points[loc.row() * self.size + loc.col()]
     }
 
     pub fn add<L, S>(&mut self, loc: L, s: S) -> Option<Stone>
@@ -52,7 +81,11 @@ impl Board {
         let loc = loc.as_ref();
         let s = s.as_ref();
         assert!(self.validloc(loc));
-        self.points.insert(*loc, *s)
+        let i = loc.row() * self.size + loc.col();
+        let old = ::std::mem::replace(&mut self.points[i], Some(*s));
+        if let Some(o) = old { self.zobrist ^= zobrist(loc, o) }
+        self.zobrist ^= zobrist(loc, *s);
+        old
     }
 
     pub fn play<L, S>(&mut self, loc: L, s: S) -> bool
@@ -74,36 +107,32 @@ impl Board {
         let ps = self.add(loc, s);
         assert!(ps.is_none());
 
-        // get resulting groups for each colour
-        let (same, opposite): (Vec<_>, Vec<_>) = {
-                let points = self.points.iter()
-                    .map(|(l, c)| (*l, *c));
-
-                GroupIterator::new(points)
-                    .partition(|g| g.colour() == s)
-        };
+        // Only the groups next to the new stone can have lost their last
+        // liberty, so find opposite coloured ones which were killed and
+        // remove them
+        let neighbours: Vec<_> = loc.neighbours().filter(|n| self.validloc(n)).collect();
+        for n in neighbours {
+            if self.get(n) != Some(!s) { continue }
 
-        // find opposite coloured groups killed and remove them
-        for g in &opposite {
-            let lib: One<_> = self.liberties(g);
+            let g = self.group_at(n).expect("missing neighbour group");
+            let lib: One<_> = self.liberties(&g);
             if lib.is_empty() {
                 for d in g.locations() {
-                    let ds = self.points.remove(&d);
+                    let ds = self.remove(&d);
                     assert_eq!(ds, Some(!s));
+                    self.captures[idx(s)] += 1;
                 }
             }
         }
 
         // See if same-coloured group containing loc is now dead
-        for g in &same {
-            if !g.contains(loc) { continue }
-
-            let lib: One<_> = self.liberties(g);
-            if lib.is_empty() {
-                for d in g.locations() {
-                    let ds = self.points.remove(&d);
-                    assert_eq!(ds, Some(s));
-                }
+        let g = self.group_at(loc).expect("missing played stone");
+        let lib: One<_> = self.liberties(&g);
+        if lib.is_empty() {
+            for d in g.locations() {
+                let ds = self.remove(&d);
+                assert_eq!(ds, Some(s));
+                self.captures[idx(!s)] += 1;
             }
         }
 
@@ -111,7 +140,11 @@ impl Board {
     }
 
     pub fn remove(&mut self, loc: &Location) -> Option<Stone> {
-        self.points.remove(loc)
+        if !self.validloc(loc) { return None }
+        let i = loc.row() * self.size + loc.col();
+        let old = self.points[i].take();
+        if let Some(o) = old { self.zobrist ^= zobrist(loc, o) }
+        old
     }
 
     pub fn locations(&self) -> AllLocations {
@@ -125,9 +158,9 @@ impl Board {
     pub fn groups<GO>(&self, colour: Stone) -> GO
         where GO: FromIterator<Group>
     {
-        let points = self.points.iter()
-            .filter(|&(_, c)| *c == colour)
-            .map(|(l, c)| (*l, *c));
+        let points = self.locations()
+            .filter(|l| self.get(l) == Some(colour))
+            .map(|l| (l, colour));
         Group::groups(points)
     }
 
@@ -139,6 +172,134 @@ impl Board {
             .filter(|l| self.get(l).is_none())
             .collect()
     }
+
+    /// Group containing the stone at `loc`, if there is one
+    pub fn group_at<L>(&self, loc: L) -> Option<Group>
+        where L: AsRef<Location>
+    {
+        let loc = *loc.as_ref();
+        let colour = match self.get(loc) {
+            Some(c) => c,
+            None => return None,
+        };
+
+        let mut stones = PointSet::new();
+        let mut fringe = vec![loc];
+        let _ = stones.insert(loc);
+
+        while let Some(l) = fringe.pop() {
+            for n in l.neighbours() {
+                if self.get(n) == Some(colour) && stones.insert(n) {
+                    fringe.push(n)
+                }
+            }
+        }
+
+        Some(Group::from_locations(colour, stones.iter()))
+    }
+
+    /// Is `loc` an empty point whose neighbours are all `colour` stones?
+    pub fn eyelike<L>(&self, loc: L, colour: Stone) -> bool
+        where L: AsRef<Location>
+    {
+        let loc = loc.as_ref();
+        self.validloc(loc) && self.get(loc).is_none() &&
+        loc.neighbours()
+            .filter(|n| self.validloc(n))
+            .all(|n| self.get(n) == Some(colour))
+    }
+
+    /// Is `loc` an eye for `colour` which can't be made false?
+    ///
+    /// This uses the usual diagonal heuristic: an eye in the centre is false
+    /// if the opponent holds two of its diagonals, and one on the edge or in
+    /// the corner is false if the opponent holds any of them.
+    pub fn true_eye<L>(&self, loc: L, colour: Stone) -> bool
+        where L: AsRef<Location>
+    {
+        let loc = loc.as_ref();
+        if !self.eyelike(loc, colour) { return false }
+
+        let diag: Vec<_> = loc.diagonals().filter(|d| self.validloc(d)).collect();
+        let bad = diag.iter().filter(|d| self.get(d) == Some(!colour)).count();
+
+        if diag.len() < 4 { bad == 0 } else { bad < 2 }
+    }
+
+    /// Connected areas of empty points
+    pub fn regions<RO>(&self) -> RO
+        where RO: FromIterator<Region>
+    {
+        let mut seen = PointSet::new();
+        let mut regions = Vec::new();
+
+        for loc in self.locations() {
+            if seen.contains(loc) || self.get(loc).is_some() { continue }
+
+            let mut region = Region { points: PointSet::new(), black: false, white: false };
+            let mut fringe = vec![loc];
+            let _ = seen.insert(loc);
+
+            while let Some(l) = fringe.pop() {
+                let _ = region.points.insert(l);
+
+                for n in l.neighbours().filter(|n| self.validloc(n)) {
+                    match self.get(n) {
+                        Some(Stone::Black) => region.black = true,
+                        Some(Stone::White) => region.white = true,
+                        None => if seen.insert(n) { fringe.push(n) },
+                    }
+                }
+            }
+
+            regions.push(region)
+        }
+
+        regions.into_iter().collect()
+    }
+}
+
+/// A connected area of empty points, and the colours of the stones surrounding it
+#[derive(Debug, Clone)]
+pub struct Region {
+    points: PointSet,
+    black: bool,
+    white: bool,
+}
+
+impl Region {
+    pub fn locations(&self) -> bitidx::Iter<Location, u32> {
+        self.points.iter()
+    }
+
+    pub fn contains<L>(&self, loc: L) -> bool
+        where L: AsRef<Location>
+    {
+        self.points.contains(*loc.as_ref())
+    }
+
+    pub fn len(&self) -> usize { self.points.len() }
+
+    /// Does the region touch any stones of `colour`?
+    pub fn borders(&self, colour: Stone) -> bool {
+        match colour {
+            Stone::Black => self.black,
+            Stone::White => self.white,
+        }
+    }
+
+    /// The colour surrounding the region, if it only touches one colour
+    pub fn owner(&self) -> Option<Stone> {
+        match (self.black, self.white) {
+            (true, false) => Some(Stone::Black),
+            (false, true) => Some(Stone::White),
+            _ => None,
+        }
+    }
+}
+
+impl AsRef<PointSet> for Region {
+    fn as_ref(&self) -> &PointSet { &self.points }
 }
 
 impl FromStr for Board {
@@ -281,6 +442,73 @@ mod tests {
 
     }
 
+    #[test] fn captures() {
+        let mut b = Board::from_str("\
+. # O .
+# O . O
+. # O .
+. . . .
+").expect("OK");
+        assert!(b.play(Location::new(2,2), Black));
+        assert_eq!(b.captures(Black), 1);
+        assert_eq!(b.captures(White), 0);
+
+        // suicide gives the stones to the opponent
+        let mut b = Board::from_str("\
+. # .
+# . .
+. . .
+").expect("OK");
+        assert!(b.play(Location::new(0,2), White));
+        assert_eq!(b.captures(Black), 1);
+        assert_eq!(b.captures(White), 0);
+    }
+
+    #[test] fn group_at() {
+        let b = Board::from_str("\
+. # O .
+# # O .
+. . O O
+").expect("OK");
+        let g = b.group_at(Location::new(1, 2)).expect("group");
+        assert_eq!(g.colour(), Black);
+        assert_eq!(g.len(), 3);
+        assert!(g.contains(Location::new(1, 3)));
+        assert_eq!(b.group_at(Location::new(3, 1)).map(|g| g.len()), Some(4));
+        assert!(b.group_at(Location::new(0, 0)).is_none());
+    }
+
+    #[test] fn eyes() {
+        let b = Board::from_str("\
+. # . # .
+# . # O #
+. # . # .
+# # # . .
+. # . . .
+").expect("OK");
+        assert!(b.true_eye(Location::new(0, 4), Black));
+        assert!(b.true_eye(Location::new(0, 2), Black));
+        assert!(!b.true_eye(Location::new(2, 4), Black));   // White on a diagonal
+        assert!(b.true_eye(Location::new(1, 3), Black));
+        assert!(b.true_eye(Location::new(2, 2), Black));    // only one diagonal
+        assert!(b.eyelike(Location::new(0, 0), Black));
+        assert!(!b.eyelike(Location::new(2, 0), Black));
+    }
+
+    #[test] fn regions() {
+        let b = Board::from_str("\
+. # O .
+# # O .
+. # O O
+. # O .
+").expect("OK");
+        let r: Vec<_> = b.regions();
+        assert_eq!(r.len(), 4);
+
+        let owners = r.iter().map(|r| (r.owner(), r.len())).collect::<Vec<_>>();
+        assert_eq!(owners, vec![(Some(Black), 2), (Some(Black), 1), (Some(White), 1), (Some(White), 2)]);
+    }
+
     #[test] fn play2() {
         let mut b = Board::from_str("\
 . . .
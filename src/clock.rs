@@ -0,0 +1,518 @@
+// Time controls and game clocks.
+//
+// A time control gives each player some main time, and then possibly some
+// overtime:
+//
+// - absolute: the main time is all there is
+// - Japanese byo-yomi: a number of periods, each of which is only used up by
+//   a move taking longer than it
+// - Canadian: a number of moves which must all be played within a period,
+//   after which a fresh period starts
+// - Fischer: no overtime, but a fixed increment added after every move
+//
+// In SGF the main time is in TM and the overtime described in OT, and after
+// each move BL/WL give the time the player has left, in the main time or the
+// current period, and OB/OW the byo-yomi periods or Canadian moves left once
+// they're in overtime. GTP has `time_settings`, which can only describe
+// absolute and Canadian time, and `time_left`, which works the same way as
+// BL and OB.
+
+use std::cmp::max;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+use std::time::Duration;
+
+use sgf::{self, Node, Property};
+use sgf::property::Value;
+use stone::Stone;
+
+/// How much time the players get
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum TimeControl {
+    /// No limits
+    Unlimited,
+    /// `main` for the whole game
+    Absolute { main: Duration },
+    /// `main` time, and then `periods` periods of `period`
+    ByoYomi { main: Duration, period: Duration, periods: u32 },
+    /// `main` time, and then `stones` moves in each `period`
+    Canadian { main: Duration, period: Duration, stones: u32 },
+    /// `main` time, with `increment` added after each move
+    Fischer { main: Duration, increment: Duration },
+}
+
+/// What a player has left on their clock
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Remaining {
+    /// Time left, of the main time or the current period
+    pub time: Duration,
+    /// Byo-yomi periods or Canadian moves left, or 0 while still in the main
+    /// time
+    pub periods: u32,
+}
+
+/// Durations in SGF and GTP are in seconds
+pub fn seconds(secs: f64) -> Duration {
+    let secs = secs.max(0.0);
+    Duration::new(secs.trunc() as u64, (secs.fract() * 1e9) as u32)
+}
+
+fn as_seconds(d: Duration) -> f64 {
+    d.as_secs() as f64 + d.subsec_nanos() as f64 / 1e9
+}
+
+impl TimeControl {
+    /// Main time, which is all of it without overtime
+    pub fn main(&self) -> Duration {
+        match *self {
+            TimeControl::Unlimited => Duration::new(0, 0),
+            TimeControl::Absolute { main } |
+            TimeControl::ByoYomi { main, .. } |
+            TimeControl::Canadian { main, .. } |
+            TimeControl::Fischer { main, .. } => main,
+        }
+    }
+
+    /// Time control from the arguments to GTP `time_settings`: main time,
+    /// byo-yomi time and byo-yomi stones, which is Canadian overtime
+    pub fn from_gtp(main: u64, period: u64, stones: u32) -> TimeControl {
+        let (main, period) = (Duration::from_secs(main), Duration::from_secs(period));
+        if period == Duration::new(0, 0) {
+            TimeControl::Absolute { main: main }
+        } else if stones == 0 {
+            // GTP's way of saying there are no limits
+            TimeControl::Unlimited
+        } else {
+            TimeControl::Canadian { main: main, period: period, stones: stones }
+        }
+    }
+
+    /// Arguments for GTP `time_settings`, if it can describe this time
+    /// control. Byo-yomi becomes Canadian time with one move per period, which
+    /// is the same until a player runs over a period.
+    pub fn to_gtp(&self) -> Option<(u64, u64, u32)> {
+        match *self {
+            TimeControl::Unlimited => Some((0, 1, 0)),
+            TimeControl::Absolute { main } => Some((main.as_secs(), 0, 0)),
+            TimeControl::ByoYomi { main, period, .. } => Some((main.as_secs(), period.as_secs(), 1)),
+            TimeControl::Canadian { main, period, stones } => Some((main.as_secs(), period.as_secs(), stones)),
+            TimeControl::Fischer { .. } => None,
+        }
+    }
+
+    /// Time control from the TM and OT properties of an SGF root node. An
+    /// overtime description which isn't understood is taken to mean there
+    /// isn't any.
+    pub fn from_sgf(root: &Node) -> sgf::Result<TimeControl> {
+        let main = match root.prop("TM") {
+            Some(p) => match try!(p.value()) {
+                Value::Real(ref r) => { let r: f32 = r.into(); seconds(r as f64) },
+                _ => return Err(sgf::Error::ValueError),
+            },
+            None => return Ok(TimeControl::Unlimited),
+        };
+
+        let overtime = match root.prop("OT").and_then(|p| p.value().ok()) {
+            Some(Value::SimpleText(ref t)) => { let t: String = t.into(); t.parse().ok() },
+            _ => None,
+        };
+
+        Ok(match overtime {
+            Some(Overtime::ByoYomi(periods, period)) =>
+                TimeControl::ByoYomi { main: main, period: period, periods: periods },
+            Some(Overtime::Canadian(stones, period)) =>
+                TimeControl::Canadian { main: main, period: period, stones: stones },
+            Some(Overtime::Fischer(increment)) => TimeControl::Fischer { main: main, increment: increment },
+            None => TimeControl::Absolute { main: main },
+        })
+    }
+
+    /// Add TM and OT properties describing this time control to an SGF root
+    /// node
+    pub fn to_sgf(&self, root: &mut Node) {
+        let prop = |id: &str, v: String| Property::new(id.to_string(), vec![v.into_bytes()]);
+
+        if *self == TimeControl::Unlimited { return }
+        root.addprop(prop("TM", as_seconds(self.main()).to_string()));
+
+        let overtime = match *self {
+            TimeControl::ByoYomi { period, periods, .. } => Overtime::ByoYomi(periods, period),
+            TimeControl::Canadian { period, stones, .. } => Overtime::Canadian(stones, period),
+            TimeControl::Fischer { increment, .. } => Overtime::Fischer(increment),
+            TimeControl::Unlimited | TimeControl::Absolute { .. } => return,
+        };
+        root.addprop(prop("OT", overtime.to_string()));
+    }
+}
+
+// The overtime part of a time control, in the form of the SGF OT property
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Overtime {
+    ByoYomi(u32, Duration),
+    Canadian(u32, Duration),
+    Fischer(Duration),
+}
+
+impl FromStr for Overtime {
+    type Err = &'static str;
+
+    // OT is free text, but most programs write one of "5x30 byo-yomi",
+    // "25/600 Canadian" or "10 fischer"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let mut words = s.split_whitespace();
+        let (amount, kind) = match (words.next(), words.next()) {
+            (Some(a), Some(k)) => (a, k),
+            _ => return Err("bad overtime"),
+        };
+
+        let split = |sep: char| {
+            let mut parts = amount.splitn(2, sep);
+            match (parts.next().and_then(|n| n.parse().ok()), parts.next().and_then(|t| t.parse().ok())) {
+                // Overtime with no periods or no moves in them isn't overtime
+                (Some(0), _) => Err("bad overtime"),
+                (Some(n), Some(t)) => Ok((n, seconds(t))),
+                _ => Err("bad overtime"),
+            }
+        };
+
+        match kind {
+            "byo-yomi" | "byoyomi" => split('x').map(|(n, t)| Overtime::ByoYomi(n, t)),
+            "canadian" => split('/').map(|(n, t)| Overtime::Canadian(n, t)),
+            "fischer" => amount.parse().map(|t| Overtime::Fischer(seconds(t))).map_err(|_| "bad overtime"),
+            _ => Err("unknown overtime"),
+        }
+    }
+}
+
+impl Display for Overtime {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Overtime::ByoYomi(n, t) => write!(fmt, "{}x{} byo-yomi", n, as_seconds(t)),
+            Overtime::Canadian(n, t) => write!(fmt, "{}/{} Canadian", n, as_seconds(t)),
+            Overtime::Fischer(t) => write!(fmt, "{} fischer", as_seconds(t)),
+        }
+    }
+}
+
+/// Both players' clocks
+#[derive(Debug, Clone, PartialEq)]
+pub struct Clock {
+    control: TimeControl,
+    remaining: [Remaining; 2],
+    flagged: [bool; 2],
+}
+
+fn side(colour: Stone) -> usize {
+    match colour {
+        Stone::Black => 0,
+        Stone::White => 1,
+    }
+}
+
+impl Clock {
+    /// Clocks for the start of a game
+    pub fn new(control: TimeControl) -> Clock {
+        let start = Remaining { time: control.main(), periods: 0 };
+        let mut clock = Clock { control: control, remaining: [start; 2], flagged: [false; 2] };
+
+        // With no main time, the game starts in overtime
+        if control.main() == Duration::new(0, 0) {
+            for r in &mut clock.remaining { *r = clock.control.overtime().unwrap_or(*r) }
+        }
+        clock
+    }
+
+    pub fn control(&self) -> TimeControl { self.control }
+
+    pub fn remaining(&self, colour: Stone) -> Remaining { self.remaining[side(colour)] }
+
+    /// Set what a player has left, as from GTP `time_left` or SGF BL and OB
+    pub fn set_remaining(&mut self, colour: Stone, remaining: Remaining) {
+        self.remaining[side(colour)] = remaining;
+    }
+
+    /// Whether `colour` has run out of time
+    pub fn flagged(&self, colour: Stone) -> bool { self.flagged[side(colour)] }
+
+    /// Take the time `colour` spent on a move off their clock, returning
+    /// whether they made it in time
+    pub fn charge(&mut self, colour: Stone, used: Duration) -> bool {
+        let s = side(colour);
+        if self.flagged[s] { return false }
+        let mut r = self.remaining[s];
+
+        let ok = match self.control {
+            TimeControl::Unlimited => true,
+            TimeControl::Absolute { .. } => take(&mut r.time, used),
+            TimeControl::Fischer { increment, .. } => {
+                let ok = take(&mut r.time, used);
+                r.time += increment;
+                ok
+            },
+            TimeControl::ByoYomi { period, .. } | TimeControl::Canadian { period, .. } => {
+                let mut used = used;
+                if r.periods == 0 {
+                    // Whatever the main time doesn't cover runs into overtime
+                    if used <= r.time {
+                        r.time -= used;
+                        used = Duration::new(0, 0);
+                    } else {
+                        used -= r.time;
+                        r = self.control.overtime().expect("no overtime");
+                    }
+                }
+
+                match self.control {
+                    _ if used == Duration::new(0, 0) => true,
+                    TimeControl::ByoYomi { .. } => {
+                        // Each period the move runs over is lost, and the
+                        // next starts afresh
+                        while used > r.time && r.periods > 0 {
+                            used -= r.time;
+                            r.periods -= 1;
+                            r.time = period;
+                        }
+                        r.time = period;
+                        r.periods > 0
+                    },
+                    _ => {
+                        let ok = take(&mut r.time, used);
+                        r.periods = r.periods.saturating_sub(1);
+                        if r.periods == 0 { r = self.control.overtime().expect("no overtime") }
+                        ok
+                    },
+                }
+            },
+        };
+
+        self.remaining[s] = r;
+        if !ok { self.flagged[s] = true }
+        ok
+    }
+
+    /// A rough amount of time to spend on the next move for `colour`: a
+    /// share of the main time, or most of a byo-yomi period, or of the time
+    /// per move in a Canadian period. None for unlimited time.
+    pub fn budget(&self, colour: Stone) -> Option<Duration> {
+        let r = self.remaining(colour);
+        let share = |d: Duration, n: u32| d / max(n, 1);
+        // Four fifths, dividing first so that huge times from GTP or SGF
+        // can't overflow
+        let most = |d: Duration| d / 5 * 4;
+        let add = |a: Duration, b: Duration| a.checked_add(b).unwrap_or_else(|| Duration::new(u64::max_value(), 0));
+
+        match self.control {
+            TimeControl::Unlimited => None,
+            TimeControl::Absolute { .. } => Some(share(r.time, 40)),
+            TimeControl::Fischer { increment, .. } => Some(add(share(r.time, 40), most(increment))),
+            TimeControl::ByoYomi { period, .. } => {
+                if r.periods == 0 { Some(add(share(r.time, 30), most(period))) } else { Some(most(r.time)) }
+            },
+            TimeControl::Canadian { period, stones, .. } => {
+                if r.periods == 0 {
+                    Some(add(share(r.time, 30), most(share(period, stones))))
+                } else {
+                    Some(most(share(r.time, r.periods)))
+                }
+            },
+        }
+    }
+
+    /// Add what `colour` has left to an SGF move node, as BL/WL and OB/OW
+    pub fn to_sgf(&self, colour: Stone, node: &mut Node) {
+        if self.control == TimeControl::Unlimited { return }
+        let r = self.remaining(colour);
+        let (time, periods) = if colour == Stone::Black { ("BL", "OB") } else { ("WL", "OW") };
+
+        node.addprop(Property::new(time.to_string(), vec![format!("{:.3}", as_seconds(r.time)).into_bytes()]));
+        if r.periods > 0 {
+            node.addprop(Property::new(periods.to_string(), vec![r.periods.to_string().into_bytes()]));
+        }
+    }
+
+    /// Set the time left from any BL/WL and OB/OW properties in an SGF node
+    pub fn update_sgf(&mut self, node: &Node) -> sgf::Result<()> {
+        for &(colour, time, periods) in &[(Stone::Black, "BL", "OB"), (Stone::White, "WL", "OW")] {
+            let mut r = self.remaining(colour);
+            if let Some(p) = node.prop(time) {
+                match try!(p.value()) {
+                    Value::Real(ref t) => { let t: f32 = t.into(); r.time = seconds(t as f64) },
+                    _ => return Err(sgf::Error::ValueError),
+                }
+                // Without OB/OW the player is in their main time
+                r.periods = 0;
+            }
+            if let Some(p) = node.prop(periods) {
+                match try!(p.value()) {
+                    Value::Number(ref n) => r.periods = n.into(),
+                    _ => return Err(sgf::Error::ValueError),
+                }
+            }
+            self.set_remaining(colour, r);
+        }
+        Ok(())
+    }
+}
+
+impl TimeControl {
+    // What a player has at the start of overtime
+    fn overtime(&self) -> Option<Remaining> {
+        match *self {
+            TimeControl::ByoYomi { period, periods, .. } => Some(Remaining { time: period, periods: periods }),
+            TimeControl::Canadian { period, stones, .. } => Some(Remaining { time: period, periods: stones }),
+            _ => None,
+        }
+    }
+}
+
+// Take `used` off `time`, returning false if there wasn't enough
+fn take(time: &mut Duration, used: Duration) -> bool {
+    if used <= *time {
+        *time -= used;
+        true
+    } else {
+        *time = Duration::new(0, 0);
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{Clock, Remaining, TimeControl, seconds};
+    use sgf::{self, Node};
+    use stone::Stone::{Black, White};
+
+    fn secs(s: u64) -> Duration { Duration::from_secs(s) }
+
+    #[test] fn absolute() {
+        let mut c = Clock::new(TimeControl::Absolute { main: secs(60) });
+        assert!(c.charge(Black, secs(50)));
+        assert_eq!(c.remaining(Black), Remaining { time: secs(10), periods: 0 });
+        assert_eq!(c.remaining(White).time, secs(60));
+        assert!(!c.charge(Black, secs(11)));
+        assert!(c.flagged(Black));
+        assert!(!c.flagged(White));
+        assert!(!c.charge(Black, secs(0)));
+    }
+
+    #[test] fn fischer() {
+        let mut c = Clock::new(TimeControl::Fischer { main: secs(60), increment: secs(10) });
+        assert!(c.charge(White, secs(30)));
+        assert_eq!(c.remaining(White).time, secs(40));
+        assert!(c.charge(White, secs(35)));
+        assert_eq!(c.remaining(White).time, secs(15));
+        assert!(!c.charge(White, secs(16)));
+        assert!(c.flagged(White));
+    }
+
+    #[test] fn byo_yomi() {
+        let mut c = Clock::new(TimeControl::ByoYomi { main: secs(60), period: secs(30), periods: 3 });
+        assert!(c.charge(Black, secs(50)));
+
+        // 10s of main time and 20s of the first period
+        assert!(c.charge(Black, secs(30)));
+        assert_eq!(c.remaining(Black), Remaining { time: secs(30), periods: 3 });
+
+        // A period isn't lost by using part of it, but is by running over
+        assert!(c.charge(Black, secs(29)));
+        assert_eq!(c.remaining(Black), Remaining { time: secs(30), periods: 3 });
+        assert!(c.charge(Black, secs(31)));
+        assert_eq!(c.remaining(Black), Remaining { time: secs(30), periods: 2 });
+        assert!(c.charge(Black, secs(45)));
+        assert_eq!(c.remaining(Black), Remaining { time: secs(30), periods: 1 });
+        assert!(!c.charge(Black, secs(31)));
+        assert!(c.flagged(Black));
+    }
+
+    #[test] fn canadian() {
+        let mut c = Clock::new(TimeControl::Canadian { main: secs(0), period: secs(60), stones: 3 });
+        assert_eq!(c.remaining(White), Remaining { time: secs(60), periods: 3 });
+        assert!(c.charge(White, secs(20)));
+        assert!(c.charge(White, secs(20)));
+        assert_eq!(c.remaining(White), Remaining { time: secs(20), periods: 1 });
+
+        // The third move finishes the period, and a new one starts
+        assert!(c.charge(White, secs(15)));
+        assert_eq!(c.remaining(White), Remaining { time: secs(60), periods: 3 });
+        assert!(c.charge(White, secs(50)));
+        assert!(!c.charge(White, secs(11)));
+        assert!(c.flagged(White));
+    }
+
+    #[test] fn budget() {
+        let c = Clock::new(TimeControl::Unlimited);
+        assert_eq!(c.budget(Black), None);
+        let c = Clock::new(TimeControl::Absolute { main: secs(400) });
+        assert_eq!(c.budget(Black), Some(secs(10)));
+        let mut c = Clock::new(TimeControl::ByoYomi { main: secs(0), period: secs(10), periods: 5 });
+        assert_eq!(c.budget(Black), Some(secs(8)));
+        c.set_remaining(White, Remaining { time: secs(300), periods: 0 });
+        assert_eq!(c.budget(White), Some(secs(18)));
+
+        // Times at the limit of what GTP can give don't overflow
+        let huge = Duration::from_secs(u64::max_value());
+        let mut c = Clock::new(TimeControl::Canadian { main: huge, period: huge, stones: u32::max_value() });
+        assert!(c.budget(Black).is_some());
+        c.set_remaining(Black, Remaining { time: huge, periods: u32::max_value() });
+        assert!(c.budget(Black).is_some());
+        let c = Clock::new(TimeControl::Fischer { main: huge, increment: huge });
+        assert!(c.budget(Black).is_some());
+
+        // Canadian time with no moves per period is nonsense, but doesn't panic
+        let mut c = Clock::new(TimeControl::Canadian { main: secs(0), period: secs(60), stones: 0 });
+        assert_eq!(c.budget(Black), Some(secs(50)));
+        let _ = c.charge(Black, secs(10));
+        c.set_remaining(White, Remaining { time: secs(60), periods: 0 });
+        assert!(c.budget(White).is_some());
+    }
+
+    #[test] fn gtp() {
+        assert_eq!(TimeControl::from_gtp(300, 0, 0), TimeControl::Absolute { main: secs(300) });
+        assert_eq!(TimeControl::from_gtp(0, 1, 0), TimeControl::Unlimited);
+        let canadian = TimeControl::Canadian { main: secs(600), period: secs(300), stones: 25 };
+        assert_eq!(TimeControl::from_gtp(600, 300, 25), canadian);
+        assert_eq!(canadian.to_gtp(), Some((600, 300, 25)));
+        assert_eq!(TimeControl::ByoYomi { main: secs(60), period: secs(30), periods: 5 }.to_gtp(), Some((60, 30, 1)));
+        assert_eq!(TimeControl::Fischer { main: secs(60), increment: secs(5) }.to_gtp(), None);
+        assert_eq!(TimeControl::Unlimited.to_gtp(), Some((0, 1, 0)));
+    }
+
+    fn root(txt: &str) -> Node {
+        sgf::parser(txt.as_bytes()).expect("parse").remove(0)
+    }
+
+    #[test] fn sgf() {
+        let controls = [
+            ("(;TM[7200]OT[3x60 byo-yomi])", TimeControl::ByoYomi { main: secs(7200), period: secs(60), periods: 3 }),
+            ("(;TM[600]OT[25/300 Canadian])", TimeControl::Canadian { main: secs(600), period: secs(300), stones: 25 }),
+            ("(;TM[300]OT[10 fischer])", TimeControl::Fischer { main: secs(300), increment: secs(10) }),
+            ("(;TM[1800])", TimeControl::Absolute { main: secs(1800) }),
+            ("(;TM[1800]OT[sudden death])", TimeControl::Absolute { main: secs(1800) }),
+            ("(;TM[1800]OT[0/300 Canadian])", TimeControl::Absolute { main: secs(1800) }),
+            ("(;TM[1800]OT[0x30 byo-yomi])", TimeControl::Absolute { main: secs(1800) }),
+            ("(;GM[1])", TimeControl::Unlimited),
+        ];
+        for &(txt, tc) in &controls {
+            assert_eq!(TimeControl::from_sgf(&root(txt)).expect("time control"), tc, "{}", txt);
+
+            // Written out and read back in, it's the same
+            let mut node = Node::new();
+            tc.to_sgf(&mut node);
+            assert_eq!(TimeControl::from_sgf(&root(&node.to_string())).expect("round trip"), tc, "{}", txt);
+        }
+        assert!(TimeControl::from_sgf(&root("(;TM[soon])")).is_err());
+
+        let mut c = Clock::new(controls[0].1);
+        c.update_sgf(&root("(;B[aa]BL[25.5]OB[2])")).expect("update");
+        assert_eq!(c.remaining(Black), Remaining { time: seconds(25.5), periods: 2 });
+        assert_eq!(c.remaining(White), Remaining { time: secs(7200), periods: 0 });
+
+        let mut node = Node::new();
+        c.to_sgf(Black, &mut node);
+        c.to_sgf(White, &mut node);
+        assert_eq!(node.to_string(), "(;BL[25.500]OB[2]WL[7200.000])");
+    }
+}
@@ -0,0 +1,281 @@
+// Classify enclosed eye spaces by their shape.
+//
+// Small eye spaces are either dead however the owner plays, alive however the
+// opponent plays, or unsettled: whoever plays first on the vital point decides
+// whether the group gets two eyes. The well known shapes are matched up to
+// rotation and reflection against a table; for living shapes the vital points
+// are the miai pair which the owner answers to keep two eyes.
+
+use board::{Board, PointSet};
+use location::Location;
+use pattern::transform;
+use stone::Stone;
+
+/// Eye spaces bigger than this are open areas rather than eyes
+pub const MAX_EYE: usize = 7;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Shape {
+    Single,
+    Two,
+    StraightThree,
+    BentThree,
+    SquareFour,
+    /// T shaped four, also called bulky four
+    PyramidFour,
+    StraightFour,
+    BentFour,
+    ZigzagFour,
+    BulkyFive,
+    /// Plus shaped five, also called flower five
+    CrossedFive,
+    /// Also called flower six
+    RabbitySix,
+    RectangularSix,
+    /// Any other shape; all of these are big enough for two eyes
+    Other,
+}
+
+/// Whether an eye space can make two eyes
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Life {
+    Dead,
+    Unsettled,
+    Alive,
+}
+
+impl Shape {
+    /// Status of the shape when it is completely empty
+    pub fn life(&self) -> Life {
+        use self::Shape::*;
+
+        match *self {
+            Single | Two | SquareFour => Life::Dead,
+            StraightThree | BentThree | PyramidFour | BulkyFive | CrossedFive | RabbitySix => Life::Unsettled,
+            StraightFour | BentFour | ZigzagFour | RectangularSix | Other => Life::Alive,
+        }
+    }
+}
+
+// Shapes drawn with X for a point of the eye space and * for a vital point
+lazy_static!{
+    static ref SHAPES: Vec<(Shape, Vec<(i32, i32)>, Vec<(i32, i32)>)> = {
+        use self::Shape::*;
+
+        vec![
+            (Single, "X"),
+            (Two, "XX"),
+            (StraightThree, "X*X"),
+            (BentThree, "*X\nX."),
+            (SquareFour, "XX\nXX"),
+            (PyramidFour, "X*X\n.X."),
+            (StraightFour, "X**X"),
+            (BentFour, "X..\n**X"),
+            (ZigzagFour, "X*.\n.*X"),
+            (BulkyFive, "XX.\nX*X"),
+            (CrossedFive, ".X.\nX*X\n.X."),
+            (RabbitySix, ".X.\nX*X\n.XX"),
+            (RectangularSix, "XXX\nXXX"),
+        ].into_iter().map(|(shape, pic)| {
+            let mut points = Vec::new();
+            let mut vital = Vec::new();
+            for (y, line) in pic.lines().enumerate() {
+                for (x, c) in line.chars().enumerate() {
+                    let p = (x as i32, y as i32);
+                    match c {
+                        'X' => points.push(p),
+                        '*' => { points.push(p); vital.push(p) },
+                        _ => (),
+                    }
+                }
+            }
+            points.sort();
+            (shape, points, vital)
+        }).collect()
+    };
+}
+
+#[derive(Debug, Clone)]
+pub struct EyeSpace {
+    points: PointSet,
+    shape: Shape,
+    life: Life,
+    vital: Vec<Location>,
+}
+
+impl EyeSpace {
+    /// Classify an empty eye space made up of `locs`
+    pub fn new<LI>(locs: LI) -> EyeSpace
+        where LI: IntoIterator<Item=Location>
+    {
+        let points: PointSet = locs.into_iter().collect();
+        let (shape, vital) = EyeSpace::find(&points);
+
+        EyeSpace {
+            points: points,
+            shape: shape,
+            life: shape.life(),
+            vital: vital,
+        }
+    }
+
+    // Match the points against each orientation of the known shapes
+    fn find(points: &PointSet) -> (Shape, Vec<Location>) {
+        for &(shape, ref pattern, ref vital) in SHAPES.iter() {
+            if pattern.len() != points.len() { continue }
+
+            for t in 0..8 {
+                let mut moved: Vec<_> = points.iter()
+                    .map(|l| (transform(t, (l.col() as i32, l.row() as i32)), l))
+                    .collect();
+                let minx = moved.iter().map(|&((x, _), _)| x).min().unwrap_or(0);
+                let miny = moved.iter().map(|&((_, y), _)| y).min().unwrap_or(0);
+                for m in &mut moved { (m.0).0 -= minx; (m.0).1 -= miny; }
+                moved.sort_by_key(|&(p, _)| p);
+
+                if moved.iter().map(|&(p, _)| p).eq(pattern.iter().cloned()) {
+                    let vital = moved.iter()
+                        .filter(|&&(p, _)| vital.contains(&p))
+                        .map(|&(_, l)| l)
+                        .collect();
+                    return (shape, vital)
+                }
+            }
+        }
+
+        (Shape::Other, Vec::new())
+    }
+
+    // Take account of opponent stones already inside the eye space
+    fn occupied(mut self, board: &Board, colour: Stone) -> EyeSpace {
+        let before = self.vital.len();
+        self.vital.retain(|l| board.get(l) != Some(!colour));
+
+        if self.vital.len() < before {
+            self.life = match self.life {
+                Life::Unsettled if self.vital.is_empty() => Life::Dead,
+                Life::Alive => Life::Unsettled,
+                l => l,
+            };
+        }
+        self
+    }
+
+    pub fn shape(&self) -> Shape { self.shape }
+    pub fn life(&self) -> Life { self.life }
+
+    /// Points which decide the status of the eye space
+    pub fn vital(&self) -> &[Location] { &self.vital }
+
+    pub fn points(&self) -> &PointSet { &self.points }
+
+    pub fn contains<L>(&self, loc: L) -> bool
+        where L: AsRef<Location>
+    {
+        self.points.contains(*loc.as_ref())
+    }
+
+    pub fn len(&self) -> usize { self.points.len() }
+}
+
+/// The eye spaces of `colour`: small connected areas of points which are empty
+/// or hold opponent stones, surrounded by `colour`'s stones and the edge
+pub fn eye_spaces(board: &Board, colour: Stone) -> Vec<EyeSpace> {
+    let mut seen = PointSet::new();
+    let mut spaces = Vec::new();
+
+    for loc in board.locations() {
+        if board.get(loc) == Some(colour) || !seen.insert(loc) { continue }
+
+        let mut area = PointSet::new();
+        let mut enclosed = false;
+        let mut fringe = vec![loc];
+        while let Some(l) = fringe.pop() {
+            let _ = area.insert(l);
+            for n in l.neighbours().filter(|n| board.validloc(n)) {
+                if board.get(n) == Some(colour) {
+                    enclosed = true
+                } else if seen.insert(n) {
+                    fringe.push(n)
+                }
+            }
+        }
+
+        if enclosed && area.len() <= MAX_EYE && area.iter().any(|l| board.get(l).is_none()) {
+            spaces.push(EyeSpace::new(area.iter()).occupied(board, colour))
+        }
+    }
+
+    spaces
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{EyeSpace, Shape, Life, eye_spaces};
+    use board::Board;
+    use location::Location;
+    use stone::Stone::{Black, White};
+
+    fn space(pts: &[(usize, usize)]) -> EyeSpace {
+        EyeSpace::new(pts.iter().map(Location::from))
+    }
+
+    #[test] fn shapes() {
+        let s = space(&[(3, 3), (3, 4), (3, 5)]);
+        assert_eq!(s.shape(), Shape::StraightThree);
+        assert_eq!(s.life(), Life::Unsettled);
+        assert_eq!(s.vital(), &[Location::new(3, 4)]);
+
+        let s = space(&[(0, 0), (1, 0), (1, 1)]);
+        assert_eq!(s.shape(), Shape::BentThree);
+        assert_eq!(s.vital(), &[Location::new(1, 0)]);
+
+        let s = space(&[(4, 4), (5, 4), (4, 5), (5, 5)]);
+        assert_eq!(s.shape(), Shape::SquareFour);
+        assert_eq!(s.life(), Life::Dead);
+        assert!(s.vital().is_empty());
+
+        let s = space(&[(2, 2), (3, 2), (4, 2), (5, 2)]);
+        assert_eq!(s.shape(), Shape::StraightFour);
+        assert_eq!(s.life(), Life::Alive);
+        assert_eq!(s.vital(), &[Location::new(3, 2), Location::new(4, 2)]);
+
+        // Rabbity six, rotated
+        let s = space(&[(1, 0), (0, 1), (1, 1), (2, 1), (0, 2), (1, 2)]);
+        assert_eq!(s.shape(), Shape::RabbitySix);
+        assert_eq!(s.vital(), &[Location::new(1, 1)]);
+
+        let s = space(&[(0, 0), (1, 0), (2, 0), (3, 0), (4, 0)]);
+        assert_eq!(s.shape(), Shape::Other);
+        assert_eq!(s.life(), Life::Alive);
+    }
+
+    #[test] fn on_board() {
+        let b = Board::from_str("\
+. . . . . .
+. . . . . .
+. . . . . .
+# # # . . .
+. . # # . .
+. . . # . .
+").expect("board");
+
+        let spaces = eye_spaces(&b, Black);
+        assert_eq!(spaces.len(), 1);
+        assert_eq!(spaces[0].shape(), Shape::BulkyFive);
+        assert_eq!(spaces[0].life(), Life::Unsettled);
+        assert_eq!(spaces[0].vital(), &[Location::new(1, 0)]);
+        assert!(eye_spaces(&b, White).is_empty());
+
+        // White has taken the vital point
+        let mut b = b;
+        let _ = b.add(Location::new(1, 0), White);
+        let spaces = eye_spaces(&b, Black);
+        assert_eq!(spaces.len(), 1);
+        assert_eq!(spaces[0].shape(), Shape::BulkyFive);
+        assert_eq!(spaces[0].life(), Life::Dead);
+        assert!(spaces[0].vital().is_empty());
+    }
+}
@@ -0,0 +1,259 @@
+// Input planes for neural networks, in the style of AlphaGo.
+//
+// Every plane has one value per point, 1.0 where the feature holds and 0.0
+// elsewhere, and everything is seen from the side of the player to move:
+// "own" stones are theirs and the move features describe them playing there.
+// Features which count something are spread over several planes, one per
+// value, with the last plane taking every value beyond it.
+//
+// The encoding is a single flat vector: plane by plane, and within a plane row
+// by row from the first line (A1, B1, ...), so the value for plane `p` at
+// `loc` is at `(p * size + loc.row()) * size + loc.col()`.
+
+use board::Board;
+use game::{Game, Move};
+use location::Location;
+use pattern;
+use stone::Stone;
+use tactics;
+
+/// Own stones, opponent's stones and empty points
+pub const STONES: usize = 0;
+/// All ones, which lets convolutions see the edge of the board
+pub const ONES: usize = 3;
+/// Stones played 1, 2, ... 8 or more moves ago
+pub const TURNS_SINCE: usize = 4;
+/// Stones whose groups have 1, 2, ... 8 or more liberties
+pub const LIBERTIES: usize = 12;
+/// Legal moves capturing 0, 1, ... 7 or more stones
+pub const CAPTURE_SIZE: usize = 20;
+/// Legal moves leaving a group of 1, 2, ... 8 or more stones in atari
+pub const SELF_ATARI: usize = 28;
+/// Moves which catch an opponent's group in a ladder
+pub const LADDER_CAPTURE: usize = 36;
+/// Moves which rescue a group from atari for good
+pub const LADDER_ESCAPE: usize = 37;
+/// Legal moves
+pub const LEGAL: usize = 38;
+/// Legal moves which don't fill one of the player's own eyes
+pub const SENSIBLE: usize = 39;
+
+/// Number of planes
+pub const PLANES: usize = 40;
+
+// Planes being filled in for a board of a given size
+struct Planes {
+    size: usize,
+    data: Vec<f32>,
+}
+
+impl Planes {
+    fn set(&mut self, plane: usize, loc: Location) {
+        self.data[(plane * self.size + loc.row()) * self.size + loc.col()] = 1.0;
+    }
+
+    // Set the plane for `n` in a group of `count` planes starting at `first`
+    // whose values start at `min`
+    fn count(&mut self, first: usize, count: usize, min: usize, n: usize, loc: Location) {
+        self.set(first + n.saturating_sub(min).min(count - 1), loc);
+    }
+}
+
+/// Encode the position in `game` for the player to move
+pub fn encode(game: &Game) -> Vec<f32> {
+    let board = game.board();
+    let colour = game.tomove();
+    let size = board.size();
+    let mut p = Planes { size: size, data: vec![0.0; PLANES * size * size] };
+
+    for l in board.locations() {
+        p.set(ONES, l);
+
+        match board.get(l) {
+            Some(s) => {
+                p.set(STONES + if s == colour { 0 } else { 1 }, l);
+                let g = board.group_at(l).expect("missing group");
+                p.count(LIBERTIES, 8, 1, board.liberties::<Vec<_>>(&g).len(), l);
+                p.set(TURNS_SINCE + 7, l);
+            },
+            None => {
+                p.set(STONES + 2, l);
+                moves(game, board, colour, l, &mut p);
+            },
+        }
+    }
+
+    // Stones played recently; those which haven't been played in the game
+    // were set up, and count as played long ago
+    let mut seen = vec![false; size * size];
+    for (ago, t) in game.history().iter().rev().enumerate().take(7) {
+        if let Move::Play(l) = t.mv() {
+            let i = l.row() * size + l.col();
+            if seen[i] || board.get(l).is_none() { continue }
+            seen[i] = true;
+
+            p.data[((TURNS_SINCE + 7) * size + l.row()) * size + l.col()] = 0.0;
+            p.set(TURNS_SINCE + ago, l);
+        }
+    }
+
+    p.data
+}
+
+// Features of `colour` playing at the empty point `l`
+fn moves(game: &Game, board: &Board, colour: Stone, l: Location, p: &mut Planes) {
+    let after = match game.check(colour, Move::Play(l)) { Ok(b) => b, Err(_) => return };
+    p.set(LEGAL, l);
+    if !(board.eyelike(l, colour) && board.true_eye(l, colour)) { p.set(SENSIBLE, l) }
+
+    p.count(CAPTURE_SIZE, 8, 0, after.captures(colour) - board.captures(colour), l);
+
+    if let Some(g) = after.group_at(l) {
+        if after.liberties::<Vec<_>>(&g).len() == 1 { p.count(SELF_ATARI, 8, 1, g.len(), l) }
+    }
+
+    if tactics::ladder_capture(board, l, colour) { p.set(LADDER_CAPTURE, l) }
+    if tactics::ladder_escape(board, l, colour) { p.set(LADDER_ESCAPE, l) }
+}
+
+/// Where `loc` goes under one of the 8 rotations and reflections of the board
+pub fn transform_loc(loc: Location, size: usize, t: usize) -> Location {
+    // Twice the offset from the centre, so it's whole on even sized boards
+    let centre = size as i32 - 1;
+    let (x, y) = pattern::transform(t, (2 * loc.col() as i32 - centre, 2 * loc.row() as i32 - centre));
+    Location::new(((x + centre) / 2) as usize, ((y + centre) / 2) as usize)
+}
+
+/// Planes encoded by `encode` for a board of `size`, rotated or reflected by
+/// symmetry `t` (0 to 7)
+pub fn transform(planes: &[f32], size: usize, t: usize) -> Vec<f32> {
+    let area = size * size;
+    let mut out = vec![0.0; planes.len()];
+
+    for row in 0..size {
+        for col in 0..size {
+            let to = transform_loc(Location::new(col, row), size, t);
+            let (i, j) = (row * size + col, to.row() * size + to.col());
+            for p in 0..planes.len() / area {
+                out[p * area + j] = planes[p * area + i];
+            }
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use board::Board;
+    use game::{Game, Move};
+    use location::Location;
+    use rules::Ruleset;
+    use stone::Stone::{Black, White};
+
+    fn loc(s: &str) -> Location { Location::from_str(s).unwrap() }
+
+    fn at(planes: &[f32], size: usize, plane: usize, l: &str) -> f32 {
+        let l = loc(l);
+        planes[(plane * size + l.row()) * size + l.col()]
+    }
+
+    #[test] fn features() {
+        let board = Board::from_str("\
+. . . . .
+O # . . .
+# O O . .
+. # # . .
+. . . . .
+").expect("board");
+        let mut game = Game::new(5, Ruleset::chinese());
+        for l in board.locations() { game.setup(l, board.get(l)); }
+        game.play(White, Move::Play(loc("E5"))).expect("play");
+        game.play(Black, Move::Play(loc("E1"))).expect("play");
+
+        // White to move
+        let f = encode(&game);
+        assert_eq!(f.len(), PLANES * 25);
+        assert_eq!(f[ONES * 25..(ONES + 1) * 25].iter().sum::<f32>(), 25.0);
+
+        assert_eq!(at(&f, 5, STONES, "B3"), 1.0);
+        assert_eq!(at(&f, 5, STONES + 1, "A3"), 1.0);
+        assert_eq!(at(&f, 5, STONES + 2, "D3"), 1.0);
+        assert_eq!(at(&f, 5, STONES, "A3"), 0.0);
+
+        assert_eq!(at(&f, 5, TURNS_SINCE, "E1"), 1.0);
+        assert_eq!(at(&f, 5, TURNS_SINCE + 1, "E5"), 1.0);
+        assert_eq!(at(&f, 5, TURNS_SINCE + 7, "B3"), 1.0);
+        assert_eq!(at(&f, 5, TURNS_SINCE + 7, "E1"), 0.0);
+
+        // The White pair has two liberties, C4 and D3; A3 and A4 one each
+        assert_eq!(at(&f, 5, LIBERTIES + 1, "C3"), 1.0);
+        assert_eq!(at(&f, 5, LIBERTIES, "A3"), 1.0);
+        assert_eq!(at(&f, 5, LIBERTIES, "A4"), 1.0);
+
+        // Playing A2 captures A3
+        assert_eq!(at(&f, 5, CAPTURE_SIZE + 1, "A2"), 1.0);
+        assert_eq!(at(&f, 5, CAPTURE_SIZE, "A2"), 0.0);
+        assert_eq!(at(&f, 5, CAPTURE_SIZE, "D4"), 1.0);
+
+        // A5 leaves A4 and A5 in atari at B5
+        assert_eq!(at(&f, 5, SELF_ATARI + 1, "A5"), 1.0);
+        assert_eq!(at(&f, 5, SELF_ATARI + 1, "D3"), 0.0);
+
+        assert_eq!(at(&f, 5, LEGAL, "D3"), 1.0);
+        assert_eq!(at(&f, 5, SENSIBLE, "D3"), 1.0);
+        assert_eq!(at(&f, 5, LEGAL, "B3"), 0.0);
+    }
+
+    #[test] fn ladders() {
+        let board = Board::from_str("\
+. . . . . . .
+. . . . . . .
+. . # . . . .
+. # O . . . .
+. . . # . . .
+. . . . . . .
+. . . . . . .
+").expect("board");
+        let mut game = Game::new(7, Ruleset::chinese());
+        for l in board.locations() { game.setup(l, board.get(l)); }
+
+        let f = encode(&game);
+        assert_eq!(at(&f, 7, LADDER_CAPTURE, "D4"), 1.0);
+        assert_eq!(at(&f, 7, LADDER_CAPTURE, "C3"), 1.0);
+        assert_eq!(f[LADDER_ESCAPE * 49..(LADDER_ESCAPE + 1) * 49].iter().sum::<f32>(), 0.0);
+
+        // White has nothing to catch
+        game.set_tomove(White);
+        let f = encode(&game);
+        assert_eq!(at(&f, 7, LADDER_CAPTURE, "D4"), 0.0);
+        assert_eq!(f[LADDER_CAPTURE * 49..(LADDER_CAPTURE + 1) * 49].iter().sum::<f32>(), 0.0);
+    }
+
+    #[test] fn symmetry() {
+        let b = Board::from_str("\
+. . . .
+. # . .
+. . . .
+. . . O
+").expect("board");
+        let mut game = Game::new(4, Ruleset::chinese());
+        for l in b.locations() { game.setup(l, b.get(l)); }
+        let f = encode(&game);
+
+        for t in 0..8 {
+            let ft = transform(&f, 4, t);
+            let (black, white) = (transform_loc(loc("B3"), 4, t), transform_loc(loc("D1"), 4, t));
+            assert!(["A1", "A4", "D1", "D4"].contains(&&white.to_string()[..]));
+            assert_eq!(at(&ft, 4, STONES, &black.to_string()), 1.0);
+            assert_eq!(at(&ft, 4, STONES + 1, &white.to_string()), 1.0);
+            assert_eq!(ft.iter().sum::<f32>(), f.iter().sum::<f32>());
+        }
+        assert_eq!(transform(&f, 4, 0), f);
+        assert_eq!(transform_loc(loc("B3"), 4, 1), loc("C3"));
+        assert_eq!(transform_loc(loc("C2"), 5, 3), loc("C4"));
+    }
+}
@@ -0,0 +1,615 @@
+use std::result;
+use std::sync::Arc;
+use std::fmt::{self, Display};
+
+use board::Board;
+use stone::Stone;
+use location::Location;
+use rules::{Ruleset, KoRule, Scoring};
+use sgf::{self, Node, Property};
+use sgf::property::{Value, go};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Move {
+    Play(Location),
+    Pass,
+}
+
+impl Display for Move {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Move::Play(loc) => write!(fmt, "{}", loc),
+            Move::Pass => write!(fmt, "pass"),
+        }
+    }
+}
+
+/// Reasons a move can't be played
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Error {
+    OffBoard,
+    Occupied,
+    Suicide,
+    Ko,
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// A move which has been played, along with the position it was played in
+///
+/// The position is shared, so copying a game doesn't copy every board in its
+/// history.
+#[derive(Debug, Clone)]
+pub struct Turn {
+    colour: Stone,
+    mv: Move,
+    captures: usize,
+    before: Arc<Board>,
+}
+
+impl Turn {
+    pub fn colour(&self) -> Stone { self.colour }
+    pub fn mv(&self) -> Move { self.mv }
+
+    /// Number of stones the move captured
+    pub fn captures(&self) -> usize { self.captures }
+
+    /// Board position before the move was played
+    pub fn before(&self) -> &Board { &self.before }
+}
+
+/// A game in progress: a board, the rules it's played under and its history
+#[derive(Debug, Clone)]
+pub struct Game {
+    board: Board,
+    rules: Ruleset,
+    komi: f32,
+    handicap: usize,
+    tomove: Stone,
+    history: Vec<Turn>,
+}
+
+impl Game {
+    pub fn new(size: usize, rules: Ruleset) -> Game {
+        Game {
+            board: Board::new_with_size(size),
+            rules: rules,
+            komi: rules.komi(),
+            handicap: 0,
+            tomove: Stone::Black,
+            history: Vec::new(),
+        }
+    }
+
+    pub fn board(&self) -> &Board { &self.board }
+    pub fn size(&self) -> usize { self.board.size() }
+    pub fn rules(&self) -> &Ruleset { &self.rules }
+
+    pub fn komi(&self) -> f32 { self.komi }
+    pub fn set_komi(&mut self, komi: f32) { self.komi = komi }
+
+    pub fn handicap(&self) -> usize { self.handicap }
+
+    pub fn tomove(&self) -> Stone { self.tomove }
+    pub fn set_tomove(&mut self, colour: Stone) { self.tomove = colour }
+
+    pub fn history(&self) -> &[Turn] { &self.history }
+
+    /// Prisoners taken by `colour` so far
+    pub fn captures(&self, colour: Stone) -> usize { self.board.captures(colour) }
+
+    /// Number of consecutive passes which ended the move history
+    pub fn passes(&self) -> usize {
+        self.history.iter().rev().take_while(|t| t.mv == Move::Pass).count()
+    }
+
+    /// Game is over after two passes in a row
+    pub fn over(&self) -> bool { self.passes() >= 2 }
+
+    /// Place handicap stones for Black, leaving White to move
+    pub fn place_handicap<LI>(&mut self, stones: LI)
+        where LI: IntoIterator<Item=Location>
+    {
+        for loc in stones {
+            let _ = self.board.add(loc, Stone::Black);
+            self.handicap += 1;
+        }
+        if self.handicap > 0 { self.tomove = Stone::White }
+    }
+
+    /// Add or remove a stone outside of normal play, as SGF setup properties do
+    pub fn setup(&mut self, loc: Location, stone: Option<Stone>) {
+        match stone {
+            Some(s) => { let _ = self.board.add(loc, s); },
+            None => { let _ = self.board.remove(&loc); },
+        }
+    }
+
+    /// Position which would result from `colour` playing `mv`
+    pub fn check(&self, colour: Stone, mv: Move) -> Result<Board> {
+        let mut board = self.board.clone();
+
+        if let Move::Play(loc) = mv {
+            if !board.validloc(loc) { return Err(Error::OffBoard) }
+            if !board.play(loc, colour) { return Err(Error::Occupied) }
+            if board.get(loc).is_none() && !self.rules.suicide() { return Err(Error::Suicide) }
+            if self.repeated(colour, &board) { return Err(Error::Ko) }
+        }
+
+        Ok(board)
+    }
+
+    pub fn legal(&self, colour: Stone, mv: Move) -> bool {
+        self.check(colour, mv).is_ok()
+    }
+
+    pub fn play(&mut self, colour: Stone, mv: Move) -> Result<()> {
+        let board = try!(self.check(colour, mv));
+        let captures = board.captures(colour) - self.board.captures(colour);
+        let before = ::std::mem::replace(&mut self.board, board);
+
+        self.history.push(Turn { colour: colour, mv: mv, captures: captures, before: Arc::new(before) });
+        self.tomove = !colour;
+
+        Ok(())
+    }
+
+    /// Take back the last move
+    pub fn undo(&mut self) -> bool {
+        match self.history.pop() {
+            Some(t) => {
+                self.board = Arc::try_unwrap(t.before).unwrap_or_else(|b| (*b).clone());
+                self.tomove = t.colour;
+                true
+            },
+            None => false,
+        }
+    }
+
+    /// Count the position according to the scoring method of the rules
+    ///
+    /// All stones on the board are treated as alive, and empty regions are
+    /// only counted when they are bordered by a single colour.
+    pub fn score(&self) -> Score {
+        let board = &self.board;
+        let mut score = Score { black: 0.0, white: 0.0 };
+
+        for r in board.regions::<Vec<_>>() {
+            match r.owner() {
+                Some(Stone::Black) => score.black += r.len() as f32,
+                Some(Stone::White) => score.white += r.len() as f32,
+                None => (),
+            }
+        }
+
+        match self.rules.scoring() {
+            Scoring::Area =>
+                for loc in board.locations() {
+                    match board.get(loc) {
+                        Some(Stone::Black) => score.black += 1.0,
+                        Some(Stone::White) => score.white += 1.0,
+                        None => (),
+                    }
+                },
+            Scoring::Territory => {
+                score.black += board.captures(Stone::Black) as f32;
+                score.white += board.captures(Stone::White) as f32;
+            },
+        }
+
+        score.white += self.komi + self.rules.compensation().points(self.handicap);
+
+        score
+    }
+
+    // Would `board` be a forbidden repetition after `colour` has moved?
+    fn repeated(&self, colour: Stone, board: &Board) -> bool {
+        let z = board.zobrist();
+
+        match self.rules.ko() {
+            KoRule::Simple =>
+                self.history.last()
+                    .map_or(false, |t| t.colour != colour && t.before.zobrist() == z),
+            KoRule::PositionalSuperko =>
+                self.board.zobrist() == z ||
+                self.history.iter().any(|t| t.before.zobrist() == z),
+            KoRule::SituationalSuperko =>
+                self.history.iter().any(|t| t.colour == !colour && t.before.zobrist() == z),
+        }
+    }
+
+    /// Set up a new game from the game-info and setup properties of an SGF root node
+    pub fn from_sgf(root: &Node) -> sgf::Result<Game> {
+        Game::from_sgf_with_rules(root, Ruleset::default())
+    }
+
+    /// Like `from_sgf`, but playing under `rules` if the game doesn't give
+    /// any
+    pub fn from_sgf_with_rules(root: &Node, rules: Ruleset) -> sgf::Result<Game> {
+        let size = match root.prop("SZ") {
+            Some(p) => match try!(p.value()) {
+                Value::Number(ref n) => n.into(),
+                _ => return Err(sgf::Error::ValueError),
+            },
+            None => 19,
+        };
+
+        let rules = match root.prop("RU").and_then(|p| p.value().ok()) {
+            Some(Value::Rules(r)) => r,
+            _ => rules,
+        };
+
+        let mut game = Game::new(size, rules);
+
+        if let Some(p) = root.prop("KM") {
+            match try!(p.value()) {
+                Value::Real(ref k) => game.komi = k.into(),
+                _ => return Err(sgf::Error::ValueError),
+            }
+        }
+
+        if let Some(p) = root.prop("HA") {
+            match try!(p.value()) {
+                Value::Number(ref n) => game.handicap = n.into(),
+                _ => return Err(sgf::Error::ValueError),
+            }
+        }
+
+        try!(game.setup_sgf(root));
+
+        // Handicap stones are normally added with AB and don't set PL
+        if game.handicap > 1 && root.prop("PL").is_none() {
+            game.tomove = Stone::White
+        }
+
+        Ok(game)
+    }
+
+    /// Apply the setup properties (AB, AW, AE, PL) of an SGF node
+    pub fn setup_sgf(&mut self, node: &Node) -> sgf::Result<()> {
+        let size = self.size();
+
+        for &(id, stone) in &[("AE", None), ("AB", Some(Stone::Black)), ("AW", Some(Stone::White))] {
+            if let Some(p) = node.prop(id) {
+                for v in try!(p.values()) {
+                    match v.gomove().and_then(|pt| sgf_location(pt, size)) {
+                        Some(loc) => self.setup(loc, stone),
+                        None => return Err(sgf::Error::ValueError),
+                    }
+                }
+            }
+        }
+
+        if let Some(p) = node.prop("PL") {
+            match try!(p.value()) {
+                Value::Color(ref c) => self.tomove = c.into(),
+                _ => return Err(sgf::Error::ValueError),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// The game as an SGF game tree: the game info and starting position in
+    /// the root, then one node per move. The result is only recorded once
+    /// both players have passed.
+    pub fn to_sgf(&self) -> Node {
+        self.to_sgf_with(|_, _, _| ())
+    }
+
+    /// The game as an SGF game tree, as for `to_sgf`, letting `annotate` add
+    /// properties to the node for each move, given its index in the history
+    /// and the player who made it
+    pub fn to_sgf_with<F>(&self, mut annotate: F) -> Node
+        where F: FnMut(usize, Stone, &mut Node)
+    {
+        let size = self.size();
+        let prop = |id: &str, values: Vec<String>| {
+            Property::new(id.to_string(), values.into_iter().map(String::into_bytes).collect())
+        };
+
+        let mut root = Node::new();
+        root.addprop(prop("FF", vec!["4".to_string()]));
+        root.addprop(prop("GM", vec!["1".to_string()]));
+        root.addprop(prop("SZ", vec![size.to_string()]));
+        root.addprop(prop("RU", vec![self.rules.to_string()]));
+        root.addprop(prop("KM", vec![self.komi.to_string()]));
+        if self.handicap > 0 { root.addprop(prop("HA", vec![self.handicap.to_string()])) }
+        if self.over() { root.addprop(prop("RE", vec![self.score().to_string()])) }
+
+        let start = self.history.first().map_or(&self.board, |t| &*t.before);
+        for &(id, colour) in &[("AB", Stone::Black), ("AW", Stone::White)] {
+            let stones: Vec<_> = start.locations()
+                .filter(|&l| start.get(l) == Some(colour))
+                .map(|l| sgf_point(l, size))
+                .collect();
+            if !stones.is_empty() { root.addprop(prop(id, stones)) }
+        }
+
+        let first = self.history.first().map_or(self.tomove, |t| t.colour);
+        if first == Stone::White { root.addprop(prop("PL", vec!["W".to_string()])) }
+
+        // Build the line of moves from the end
+        let mut next: Option<Node> = None;
+        for (i, t) in self.history.iter().enumerate().rev() {
+            let mut node = Node::new();
+            let id = if t.colour == Stone::Black { "B" } else { "W" };
+            let point = match t.mv { Move::Play(l) => sgf_point(l, size), Move::Pass => String::new() };
+            node.addprop(prop(id, vec![point]));
+            annotate(i, t.colour, &mut node);
+            if let Some(n) = next { node.addchild(n) }
+            next = Some(node);
+        }
+        if let Some(n) = next { root.addchild(n) }
+
+        root
+    }
+}
+
+/// Final count for each side, including komi
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Score {
+    pub black: f32,
+    pub white: f32,
+}
+
+impl Score {
+    /// Black's lead; negative when White is ahead
+    pub fn margin(&self) -> f32 { self.black - self.white }
+
+    pub fn winner(&self) -> Option<Stone> {
+        let m = self.margin();
+        if m > 0.0 { Some(Stone::Black) } else if m < 0.0 { Some(Stone::White) } else { None }
+    }
+}
+
+impl Display for Score {
+    // Same form as the SGF RE property and GTP final_score, eg "B+3.5"
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match self.winner() {
+            Some(Stone::Black) => write!(fmt, "B+{}", self.margin()),
+            Some(Stone::White) => write!(fmt, "W+{}", -self.margin()),
+            None => write!(fmt, "0"),
+        }
+    }
+}
+
+/// Convert an SGF point into a board location
+///
+/// SGF rows count down from the top of the board, whereas `Location` rows
+/// count up from the bottom. Points off the board (such as the old "tt"
+/// pass) give `None`.
+pub fn sgf_location(pt: &go::Point, size: usize) -> Option<Location> {
+    let loc: Location = pt.into();
+    if loc.col() < size && loc.row() < size {
+        Some(Location::new(loc.col(), size - 1 - loc.row()))
+    } else {
+        None
+    }
+}
+
+/// Convert a board location into an SGF point, the reverse of `sgf_location`
+pub fn sgf_point(loc: Location, size: usize) -> String {
+    let coord = |c: usize| if c < 26 { (b'a' + c as u8) as char } else { (b'A' + (c - 26) as u8) as char };
+    format!("{}{}", coord(loc.col()), coord(size - 1 - loc.row()))
+}
+
+/// Extract the move played in an SGF node, if any. An empty value is a
+/// pass, as is "tt" on boards no bigger than 19x19; anything else which isn't
+/// a point on the board is an error.
+pub fn sgf_move(node: &Node, size: usize) -> Option<sgf::Result<(Stone, Move)>> {
+    for &(id, colour) in &[("B", Stone::Black), ("W", Stone::White)] {
+        if let Some(p) = node.prop(id) {
+            if p.raw().first().map_or(true, |r| r.is_empty()) { return Some(Ok((colour, Move::Pass))) }
+
+            let mv = match p.value() {
+                Ok(ref v) => match v.gomove() {
+                    Some(pt) => match sgf_location(pt, size) {
+                        Some(loc) => Ok(Move::Play(loc)),
+                        None if size <= 19 && p.raw()[0] == b"tt" => Ok(Move::Pass),
+                        None => Err(sgf::Error::ValueError),
+                    },
+                    None => Err(sgf::Error::ValueError),
+                },
+                Err(e) => Err(e),
+            };
+            return Some(mv.map(|mv| (colour, mv)))
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Game, Move, Error, Score};
+    use board::Board;
+    use location::Location;
+    use rules::Ruleset;
+    use stone::Stone::{Black, White};
+    use sgf;
+
+    fn game(pos: &str, rules: Ruleset) -> Game {
+        let board = Board::from_str(pos).expect("board");
+        let mut game = Game::new(board.size(), rules);
+        for loc in board.locations() {
+            game.setup(loc, board.get(loc));
+        }
+        game
+    }
+
+    fn play(g: &mut Game, s: &str) -> Result<(), Error> {
+        let c = g.tomove();
+        g.play(c, Move::Play(Location::from_str(s).unwrap()))
+    }
+
+    #[test] fn simple_ko() {
+        let mut g = game("\
+. # O .
+# . # O
+. # O .
+. . . .
+", Ruleset::japanese());
+        g.set_tomove(White);
+
+        assert_eq!(play(&mut g, "B3"), Ok(()));     // White captures
+        assert_eq!(g.board().get(Location::from_str("C3").unwrap()), None);
+        assert_eq!(play(&mut g, "C3"), Err(Error::Ko)); // Black can't retake immediately
+        assert_eq!(play(&mut g, "A1"), Ok(()));     // ko threat
+        assert_eq!(play(&mut g, "D1"), Ok(()));     // answer
+        assert_eq!(play(&mut g, "C3"), Ok(()));     // now Black can retake
+    }
+
+    #[test] fn suicide() {
+        let pos = "\
+. # .
+# . .
+. . .
+";
+        let mut g = game(pos, Ruleset::japanese());
+        assert_eq!(g.play(White, Move::Play(Location::new(0, 2))), Err(Error::Suicide));
+
+        let mut g = game(pos, Ruleset::new_zealand());
+        assert_eq!(g.play(White, Move::Play(Location::new(0, 2))), Ok(()));
+        assert_eq!(g.board().get(Location::new(0, 2)), None);
+    }
+
+    #[test] fn superko() {
+        // Single stone suicide recreates the same position, which positional
+        // superko forbids
+        let pos = "\
+. # .
+# . .
+. . .
+";
+        let mut g = game(pos, Ruleset::tromp_taylor());
+        assert_eq!(g.play(White, Move::Play(Location::new(0, 2))), Err(Error::Ko));
+    }
+
+    #[test] fn undo() {
+        let mut g = Game::new(5, Ruleset::chinese());
+        assert!(!g.undo());
+        assert_eq!(g.play(Black, Move::Play(Location::new(2, 2))), Ok(()));
+        assert_eq!(g.play(White, Move::Pass), Ok(()));
+        assert_eq!(g.tomove(), Black);
+        assert!(g.undo());
+        assert_eq!(g.tomove(), White);
+        assert!(g.undo());
+        assert_eq!(g.board(), &Board::new_with_size(5));
+    }
+
+    #[test] fn captures() {
+        let mut g = game("\
+. # O .
+# O . O
+. # O .
+. . . .
+", Ruleset::japanese());
+        assert_eq!(play(&mut g, "C3"), Ok(()));
+        assert_eq!(g.captures(Black), 1);
+        assert_eq!(g.history().last().map(|t| t.captures()), Some(1));
+
+        assert_eq!(play(&mut g, "A1"), Ok(()));
+        assert_eq!(g.history().last().map(|t| t.captures()), Some(0));
+        assert_eq!(g.captures(Black), 1);
+
+        assert!(g.undo());
+        assert!(g.undo());
+        assert_eq!(g.captures(Black), 0);
+        assert_eq!(g.board().get(Location::from_str("B3").unwrap()), Some(White));
+    }
+
+    #[test] fn score() {
+        let pos = "\
+. # O .
+. # O .
+. # O .
+. # O .
+";
+        let mut g = game(pos, Ruleset::chinese());
+        g.set_komi(0.5);
+        assert_eq!(g.score(), Score { black: 8.0, white: 8.5 });
+        assert_eq!(format!("{}", g.score()), "W+0.5");
+
+        // Territory scoring counts prisoners rather than stones
+        let mut g = game(pos, Ruleset::japanese());
+        g.set_komi(0.0);
+        assert_eq!(g.score(), Score { black: 4.0, white: 4.0 });
+        assert_eq!(format!("{}", g.score()), "0");
+        assert_eq!(g.play(White, Move::Play(Location::new(0, 0))), Ok(()));
+        assert_eq!(g.play(Black, Move::Play(Location::new(0, 1))), Ok(()));
+        assert_eq!(g.play(White, Move::Play(Location::new(0, 2))), Ok(()));
+        assert_eq!(g.play(Black, Move::Play(Location::new(0, 3))), Ok(()));
+        assert_eq!(g.captures(Black), 2);
+        assert_eq!(g.score(), Score { black: 4.0, white: 4.0 });
+
+        // Filling your own territory costs a point
+        assert_eq!(g.play(White, Move::Pass), Ok(()));
+        assert_eq!(g.play(Black, Move::Play(Location::new(0, 2))), Ok(()));
+        assert_eq!(format!("{}", g.score()), "W+1");
+    }
+
+    #[test] fn from_sgf() {
+        let coll = sgf::parser(b"(;GM[1]SZ[9]RU[AGA]KM[0.5]HA[2]AB[cc][gg];W[ee];B[])").expect("parse");
+        let g = Game::from_sgf(&coll[0]).expect("game");
+
+        assert_eq!(g.size(), 9);
+        assert_eq!(*g.rules(), Ruleset::aga());
+        assert_eq!(g.komi(), 0.5);
+        assert_eq!(g.handicap(), 2);
+        assert_eq!(g.tomove(), White);
+        assert_eq!(g.board().get(Location::new(2, 6)), Some(Black));
+        assert_eq!(g.board().get(Location::new(6, 2)), Some(Black));
+
+        let node = &coll[0][0];
+        assert_eq!(super::sgf_move(node, 9), Some(Ok((White, Move::Play(Location::new(4, 4))))));
+        assert_eq!(super::sgf_move(&node[0], 9), Some(Ok((Black, Move::Pass))));
+    }
+
+    #[test] fn sgf_moves() {
+        let coll = sgf::parser(b"(;SZ[9];B[tt];W[jj];B[e];W[]C[pass];B[ia])").expect("parse");
+        let mut moves = Vec::new();
+        let mut node = &coll[0];
+        assert_eq!(super::sgf_move(node, 9), None);
+        while node.len() > 0 {
+            node = &node[0];
+            moves.push(super::sgf_move(node, 9).expect("no move"));
+        }
+
+        assert_eq!(moves[0], Ok((Black, Move::Pass)));
+        assert_eq!(moves[1], Err(sgf::Error::ValueError));
+        assert_eq!(moves[2], Err(sgf::Error::ValueError));
+        assert_eq!(moves[3], Ok((White, Move::Pass)));
+        assert_eq!(moves[4], Ok((Black, Move::Play(Location::new(8, 8)))));
+
+        // "tt" is a point on bigger boards, and off smaller ones
+        assert_eq!(super::sgf_move(&coll[0][0], 21), Some(Ok((Black, Move::Play(Location::new(19, 1))))));
+    }
+
+    #[test] fn to_sgf() {
+        let mut g = Game::new(9, Ruleset::chinese());
+        g.setup(Location::new(2, 6), Some(Black));
+        g.setup(Location::new(6, 2), Some(White));
+        g.set_tomove(White);
+        assert_eq!(g.play(White, Move::Play(Location::new(4, 4))), Ok(()));
+        assert_eq!(g.play(Black, Move::Pass), Ok(()));
+
+        let txt = g.to_sgf().to_string();
+        assert_eq!(txt, "(;AB[cc]AW[gg]FF[4]GM[1]KM[7.5]PL[W]RU[Chinese]SZ[9];W[ee];B[])");
+
+        // Reading it back gives the same game
+        let coll = sgf::parser(txt.as_bytes()).expect("parse");
+        let mut h = Game::from_sgf(&coll[0]).expect("game");
+        assert_eq!(h.board(), g.history()[0].before());
+        assert_eq!(h.tomove(), White);
+        let mut node = &coll[0];
+        while node.len() > 0 {
+            node = &node[0];
+            let (c, mv) = super::sgf_move(node, 9).expect("move").expect("bad move");
+            assert_eq!(h.play(c, mv), Ok(()));
+        }
+        assert_eq!(h.board(), g.board());
+        assert_eq!(super::sgf_point(Location::new(0, 0), 19), "as");
+    }
+}
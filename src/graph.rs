@@ -0,0 +1,180 @@
+// Common fate graph: the board seen as a graph whose nodes are chains of
+// stones of either colour, or regions of empty points, with edges between
+// chains which touch. Stones in a chain live or die together, so this is the
+// natural level to reason about groups and their neighbours.
+
+use std::collections::hash_map::HashMap;
+use std::ops::Index;
+use std::slice;
+
+use bit_set::bitidx;
+
+use board::{Board, Region, PointSet};
+use group::Group;
+use location::Location;
+use stone::Stone;
+
+#[derive(Debug, Clone)]
+pub enum Chain {
+    Stones(Group),
+    Empty(Region),
+}
+
+impl Chain {
+    /// Colour of the chain's stones, or None for an empty region
+    pub fn colour(&self) -> Option<Stone> {
+        match *self {
+            Chain::Stones(ref g) => Some(g.colour()),
+            Chain::Empty(_) => None,
+        }
+    }
+
+    pub fn points(&self) -> &PointSet {
+        match *self {
+            Chain::Stones(ref g) => g.as_ref(),
+            Chain::Empty(ref r) => r.as_ref(),
+        }
+    }
+
+    pub fn locations(&self) -> bitidx::Iter<Location, u32> {
+        self.points().iter()
+    }
+
+    pub fn contains<L>(&self, loc: L) -> bool
+        where L: AsRef<Location>
+    {
+        self.points().contains(*loc.as_ref())
+    }
+
+    pub fn len(&self) -> usize { self.points().len() }
+
+    pub fn group(&self) -> Option<&Group> {
+        if let &Chain::Stones(ref g) = self { Some(g) } else { None }
+    }
+
+    pub fn region(&self) -> Option<&Region> {
+        if let &Chain::Empty(ref r) = self { Some(r) } else { None }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Graph {
+    chains: Vec<Chain>,
+    index: HashMap<Location, usize>,
+    edges: Vec<Vec<usize>>,
+}
+
+impl Graph {
+    pub fn new(board: &Board) -> Graph {
+        let mut chains: Vec<_> = board.groups::<Vec<_>>(Stone::Black).into_iter()
+            .chain(board.groups::<Vec<_>>(Stone::White))
+            .map(Chain::Stones)
+            .collect();
+        chains.extend(board.regions::<Vec<_>>().into_iter().map(Chain::Empty));
+
+        let mut index = HashMap::new();
+        for (id, c) in chains.iter().enumerate() {
+            for l in c.locations() {
+                let _ = index.insert(l, id);
+            }
+        }
+
+        let mut edges = vec![Vec::new(); chains.len()];
+        for (id, c) in chains.iter().enumerate() {
+            for l in c.locations() {
+                for n in l.neighbours().filter(|n| board.validloc(n)) {
+                    let nid = index[&n];
+                    if nid != id { edges[id].push(nid) }
+                }
+            }
+        }
+        for e in &mut edges {
+            e.sort();
+            e.dedup();
+        }
+
+        Graph {
+            chains: chains,
+            index: index,
+            edges: edges,
+        }
+    }
+
+    /// Number of chains
+    pub fn len(&self) -> usize { self.chains.len() }
+
+    pub fn chains(&self) -> slice::Iter<Chain> { self.chains.iter() }
+
+    /// Index of the chain which contains `loc`
+    pub fn chain_at<L>(&self, loc: L) -> Option<usize>
+        where L: AsRef<Location>
+    {
+        self.index.get(loc.as_ref()).cloned()
+    }
+
+    /// Indices of the chains adjacent to chain `id`
+    pub fn neighbours(&self, id: usize) -> &[usize] { &self.edges[id] }
+
+    /// Are chains `a` and `b` adjacent?
+    pub fn adjacent(&self, a: usize, b: usize) -> bool {
+        self.edges[a].binary_search(&b).is_ok()
+    }
+
+    /// Each edge in the graph, once
+    pub fn edges(&self) -> Vec<(usize, usize)> {
+        self.edges.iter().enumerate()
+            .flat_map(|(a, e)| e.iter().filter(move |&&b| a < b).map(move |&b| (a, b)))
+            .collect()
+    }
+}
+
+impl Index<usize> for Graph {
+    type Output = Chain;
+    fn index(&self, idx: usize) -> &Chain { &self.chains[idx] }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Graph;
+    use board::Board;
+    use location::Location;
+    use stone::Stone::{Black, White};
+
+    #[test] fn graph() {
+        let b = Board::from_str("\
+. # O .
+# # O .
+. O . O
+. # . .
+").expect("board");
+        let g = Graph::new(&b);
+
+        // 2 black chains, 3 white chains and 4 empty regions
+        assert_eq!(g.len(), 9);
+        assert_eq!(g.chains().filter(|c| c.colour() == Some(Black)).count(), 2);
+        assert_eq!(g.chains().filter(|c| c.colour() == Some(White)).count(), 3);
+        assert_eq!(g.chains().filter(|c| c.colour().is_none()).count(), 4);
+
+        let big = g.chain_at(Location::new(1, 2)).unwrap();
+        assert_eq!(g[big].len(), 3);
+        assert!(g[big].contains(Location::new(0, 2)));
+
+        // The big black chain touches the white wall, the lone white stone
+        // and two empty regions
+        let wall = g.chain_at(Location::new(2, 3)).unwrap();
+        let lone = g.chain_at(Location::new(1, 1)).unwrap();
+        let corner = g.chain_at(Location::new(0, 3)).unwrap();
+        assert!(g.adjacent(big, wall));
+        assert!(g.adjacent(wall, big));
+        assert!(g.adjacent(big, lone));
+        assert!(g.adjacent(big, corner));
+        assert_eq!(g.neighbours(big).len(), 4);
+        assert!(!g.adjacent(corner, wall));
+
+        let edges = g.edges();
+        assert!(edges.iter().all(|&(a, b)| a < b && g.adjacent(a, b)));
+        assert_eq!(edges.len() * 2, (0..g.len()).map(|c| g.neighbours(c).len()).sum::<usize>());
+    }
+}
@@ -25,6 +25,16 @@ impl Group {
         g
     }
 
+    /// Group of `stone` coloured stones at `locs`, which are assumed to be connected
+    pub fn from_locations<LI>(stone: Stone, locs: LI) -> Group
+        where LI: IntoIterator<Item=Location>
+    {
+        Group {
+            colour: stone,
+            group: locs.into_iter().collect(),
+        }
+    }
+
     /// Return set of locations adjacent to group stones, including internal
     pub fn neighbours(&self) -> BitSet<Location> {
         self.group.iter()
@@ -52,6 +62,9 @@ impl Group {
 
     pub fn colour(&self) -> Stone { self.colour }
 
+    /// Number of stones in the group
+    pub fn len(&self) -> usize { self.group.len() }
+
     pub fn locations(&self) -> bitidx::Iter<Location, u32> {
         self.group.iter()
     }
@@ -78,8 +91,18 @@ impl Group {
         let other = other.as_ref();
         assert!(self.group.is_disjoint(&other.group));
 
-        self.colour == other.colour &&
-        other.group.iter().any(|l| self.adjacent(other.colour, l))
+        self.colour == other.colour && self.touching(other)
+    }
+
+    /// Do any stones of the two groups touch, whatever their colour?
+    pub fn touching<G>(&self, other: G) -> bool
+        where G: AsRef<Group>
+    {
+        let other = other.as_ref();
+
+        other.group.iter()
+            .any(|l| !self.group.contains(l) &&
+                     l.neighbours().any(|n| self.group.contains(n)))
     }
 
     pub fn merge<G>(&self, other: G) -> Option<Group>
@@ -206,6 +229,20 @@ mod tests {
         assert_eq!(gs[0].locations().collect::<BitSet<_>>(), expect);
     }
 
+    #[test] fn touching() {
+        let stones = [((1, 1), Black), ((1, 2), Black), ((1, 3), White),
+                      ((2, 1), White), ((3, 3), White),];
+        let locs = stones.iter()
+                        .map(|&(loc, col)| (Location::from(loc), col));
+        let gs: Vec<_> = Group::groups(locs);
+        let find = |l: (usize, usize)| gs.iter().find(|g| g.contains(Location::from(l))).unwrap();
+
+        assert!(find((1, 1)).touching(find((1, 3))));
+        assert!(find((2, 1)).touching(find((1, 2))));
+        assert!(!find((3, 3)).touching(find((1, 1))));
+        assert!(!find((3, 3)).touching(find((1, 3))));
+    }
+
     #[test] fn neighbours() {
         let stones = [((1, 1), Black), ((1, 2), Black), ((1, 3), White),
                       ((2, 1), White), ((2, 2), Black), ((2, 3), Black),];
@@ -0,0 +1,279 @@
+// Analysis from the GTP extensions `lz-analyze` (Leela Zero) and
+// `kata-analyze` (KataGo).
+//
+// After the usual "=" line, the engine keeps printing lines of analysis until
+// it gets another command. Each line is a series of candidate moves, each
+// starting with "info" and followed by key/value pairs, the principal
+// variation last:
+//
+//     info move D4 visits 120 winrate 5321 prior 1123 lcb 5200 order 0 pv D4 Q16 ...
+//
+// Leela Zero gives winrates and priors in hundredths of a percent, whereas
+// KataGo gives them as fractions and adds a score estimate, and when asked,
+// the ownership of each point at the end of the line. Both report winrates
+// for the player the analysis is for. Keys which aren't understood are
+// skipped, along with their values.
+
+use game::Move;
+use location::Location;
+use super::parse_vertex;
+
+/// Which engine's dialect the analysis is in
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Format {
+    /// `lz-analyze`, as from Leela Zero and Leela Zero compatible engines
+    Leela,
+    /// `kata-analyze`
+    Kata,
+}
+
+impl Format {
+    /// Name of the GTP command
+    pub fn command(&self) -> &'static str {
+        match *self {
+            Format::Leela => "lz-analyze",
+            Format::Kata => "kata-analyze",
+        }
+    }
+
+    // What winrates and probabilities are divided by to make them fractions
+    fn scale(&self) -> f32 {
+        match *self {
+            Format::Leela => 10000.0,
+            Format::Kata => 1.0,
+        }
+    }
+}
+
+/// The engine's view of one candidate move
+#[derive(Debug, Clone, PartialEq)]
+pub struct MoveInfo {
+    mv: Move,
+    visits: usize,
+    winrate: f32,
+    score_lead: Option<f32>,
+    prior: Option<f32>,
+    lcb: Option<f32>,
+    order: Option<usize>,
+    pv: Vec<Move>,
+}
+
+impl MoveInfo {
+    pub fn mv(&self) -> Move { self.mv }
+    pub fn visits(&self) -> usize { self.visits }
+    /// Chance of winning, from 0 to 1
+    pub fn winrate(&self) -> f32 { self.winrate }
+    /// Expected lead in points (KataGo only)
+    pub fn score_lead(&self) -> Option<f32> { self.score_lead }
+    /// Probability the policy network gives the move, from 0 to 1
+    pub fn prior(&self) -> Option<f32> { self.prior }
+    /// Lower confidence bound of the winrate
+    pub fn lcb(&self) -> Option<f32> { self.lcb }
+    /// Where the engine ranks the move, 0 being its choice
+    pub fn order(&self) -> Option<usize> { self.order }
+    /// Principal variation, starting with the move itself
+    pub fn pv(&self) -> &[Move] { &self.pv }
+}
+
+/// One line of analysis
+#[derive(Debug, Clone, PartialEq)]
+pub struct Analysis {
+    size: usize,
+    moves: Vec<MoveInfo>,
+    ownership: Option<Vec<f32>>,
+}
+
+impl Analysis {
+    /// Parse a line of analysis of a game on a board of `size`
+    pub fn parse(line: &str, size: usize, format: Format) -> Result<Analysis, String> {
+        let mut analysis = Analysis { size: size, moves: Vec::new(), ownership: None };
+        let mut words = line.split_whitespace().peekable();
+        let scale = format.scale();
+        // Whether the last info had its move
+        let mut moved = true;
+
+        fn number<T: ::std::str::FromStr>(key: &str, word: Option<&str>) -> Result<T, String> {
+            word.and_then(|w| w.parse().ok()).ok_or_else(|| format!("bad value for {}", key))
+        }
+
+        while let Some(key) = words.next() {
+            if key == "info" {
+                if !moved { return Err("info without move".to_string()) }
+                moved = false;
+                analysis.moves.push(MoveInfo {
+                    mv: Move::Pass,
+                    visits: 0,
+                    winrate: 0.0,
+                    score_lead: None,
+                    prior: None,
+                    lcb: None,
+                    order: None,
+                    pv: Vec::new(),
+                });
+                continue
+            }
+
+            if key == "ownership" {
+                let mut own = Vec::new();
+                while let Some(v) = words.peek().and_then(|w| w.parse::<f32>().ok()) {
+                    own.push(v);
+                    let _ = words.next();
+                }
+                if own.len() != size * size {
+                    return Err(format!("{} ownership values for {} points", own.len(), size * size))
+                }
+                analysis.ownership = Some(own);
+                continue
+            }
+
+            let info = match analysis.moves.last_mut() {
+                Some(i) => i,
+                None => return Err(format!("{} before info", key)),
+            };
+            match key {
+                "move" => {
+                    info.mv = try!(words.next().and_then(|w| parse_vertex(w, size)).ok_or("bad move"));
+                    moved = true;
+                },
+                "visits" => info.visits = try!(number(key, words.next())),
+                "winrate" => info.winrate = try!(number::<f32>(key, words.next())) / scale,
+                "scoreLead" => info.score_lead = Some(try!(number(key, words.next()))),
+                // Older versions of KataGo only had the mean
+                "scoreMean" => {
+                    let mean = try!(number(key, words.next()));
+                    if info.score_lead.is_none() { info.score_lead = Some(mean) }
+                },
+                "prior" => info.prior = Some(try!(number::<f32>(key, words.next())) / scale),
+                "lcb" => info.lcb = Some(try!(number::<f32>(key, words.next())) / scale),
+                "order" => info.order = Some(try!(number(key, words.next()))),
+                "pv" => {
+                    while let Some(mv) = words.peek().and_then(|w| parse_vertex(w, size)) {
+                        info.pv.push(mv);
+                        let _ = words.next();
+                    }
+                },
+                // Keys start with a lower case letter, and no value does
+                _ => while words.peek().map_or(false, |w| !w.starts_with(|c: char| c.is_lowercase())) {
+                    let _ = words.next();
+                },
+            }
+        }
+
+        if !moved { return Err("info without move".to_string()) }
+        Ok(analysis)
+    }
+
+    /// Candidate moves, in the order the engine gave them, which is normally
+    /// best first
+    pub fn moves(&self) -> &[MoveInfo] { &self.moves }
+
+    /// The candidate the engine ranks first
+    pub fn best(&self) -> Option<&MoveInfo> {
+        self.moves.iter().min_by_key(|m| m.order.unwrap_or(usize::max_value()))
+    }
+
+    /// How much `loc` belongs to the player the analysis is for at the end of
+    /// the game, from -1 (the opponent) to 1 (them), if the engine said.
+    /// None for points off the board.
+    pub fn ownership(&self, loc: Location) -> Option<f32> {
+        if loc.row() >= self.size || loc.col() >= self.size { return None }
+        // Ownership goes row by row from the top left
+        self.ownership.as_ref().map(|o| o[(self.size - 1 - loc.row()) * self.size + loc.col()])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Analysis, Format};
+    use game::Move;
+    use location::Location;
+
+    fn loc(s: &str) -> Location { Location::from_str(s).unwrap() }
+
+    fn mv(s: &str) -> Move { Move::Play(loc(s)) }
+
+    #[test] fn leela() {
+        // From Leela Zero 0.17, on an empty 19x19 board
+        let line = "info move Q16 visits 1290 winrate 4741 prior 1737 lcb 4721 order 0 pv Q16 D4 Q3 D16 \
+                    info move D4 visits 950 winrate 4730 prior 1656 lcb 4705 order 1 pv D4 Q16 D16 Q3 \
+                    info move pass visits 1 winrate 1203 prior 12 lcb 0 order 2 pv pass Q16";
+        let a = Analysis::parse(line, 19, Format::Leela).expect("parse");
+
+        assert_eq!(a.moves().len(), 3);
+        let q16 = &a.moves()[0];
+        assert_eq!(q16.mv(), mv("Q16"));
+        assert_eq!(q16.visits(), 1290);
+        assert!((q16.winrate() - 0.4741).abs() < 1e-6);
+        assert!((q16.prior().unwrap() - 0.1737).abs() < 1e-6);
+        assert!((q16.lcb().unwrap() - 0.4721).abs() < 1e-6);
+        assert_eq!(q16.score_lead(), None);
+        assert_eq!(q16.pv(), &[mv("Q16"), mv("D4"), mv("Q3"), mv("D16")]);
+
+        assert_eq!(a.moves()[2].mv(), Move::Pass);
+        assert_eq!(a.moves()[2].pv(), &[Move::Pass, mv("Q16")]);
+        assert_eq!(a.best().map(|m| m.mv()), Some(mv("Q16")));
+        assert_eq!(a.ownership(loc("D4")), None);
+
+        // Older versions had no lcb or order
+        let a = Analysis::parse("info move C3 visits 4 winrate 5012 prior 980 pv C3 G7", 9, Format::Leela)
+            .expect("parse");
+        assert_eq!(a.moves()[0].order(), None);
+        assert_eq!(a.moves()[0].pv(), &[mv("C3"), mv("G7")]);
+        assert_eq!(a.best().map(|m| m.mv()), Some(mv("C3")));
+    }
+
+    #[test] fn kata() {
+        // From KataGo 1.12 on 9x9 with "kata-analyze B 50 ownership true",
+        // after B E5 W C4
+        let mut line = String::from(
+            "info move D6 visits 172 edgeVisits 172 utility 0.4913 winrate 0.7893 scoreMean 4.12 \
+             scoreStdev 11.3 scoreLead 4.05 scoreSelfplay 4.87 prior 0.2411 lcb 0.7745 utilityLcb 0.4461 \
+             weight 171.2 order 0 isSymmetryOf F4 pv D6 C6 D7 E3 pvVisits 172 90 51 20 \
+             info move E3 visits 64 edgeVisits 64 utility 0.4402 winrate 0.7702 scoreMean 3.62 \
+             scoreStdev 11.9 scoreLead 3.61 scoreSelfplay 4.1 prior 0.1563 lcb 0.7481 utilityLcb 0.3921 \
+             weight 63.9 order 1 pv E3 D3 pvVisits 64 31 ownership");
+        for row in 0..9 {
+            for col in 0..9 {
+                // The top left corner is Black's, and the bottom right White's
+                let own = if row < 3 && col < 3 { 0.9 } else if row > 5 && col > 5 { -0.8 } else { 0.05 };
+                line.push_str(&format!(" {}", own));
+            }
+        }
+
+        let a = Analysis::parse(&line, 9, Format::Kata).expect("parse");
+        assert_eq!(a.moves().len(), 2);
+        let d6 = &a.moves()[0];
+        assert_eq!(d6.mv(), mv("D6"));
+        assert_eq!(d6.visits(), 172);
+        assert!((d6.winrate() - 0.7893).abs() < 1e-6);
+        assert_eq!(d6.score_lead(), Some(4.05));
+        assert_eq!(d6.order(), Some(0));
+        assert_eq!(d6.pv(), &[mv("D6"), mv("C6"), mv("D7"), mv("E3")]);
+        assert_eq!(a.moves()[1].pv(), &[mv("E3"), mv("D3")]);
+
+        assert_eq!(a.ownership(loc("A9")), Some(0.9));
+        assert_eq!(a.ownership(loc("C7")), Some(0.9));
+        assert_eq!(a.ownership(loc("J1")), Some(-0.8));
+        assert_eq!(a.ownership(loc("A1")), Some(0.05));
+        assert_eq!(a.ownership(loc("K5")), None);
+        assert_eq!(a.ownership(loc("E11")), None);
+
+        // Older versions only give the mean
+        let a = Analysis::parse("info move E5 visits 10 winrate 0.51 scoreMean 0.7 prior 0.3 order 0 pv E5",
+                                9, Format::Kata).expect("parse");
+        assert_eq!(a.moves()[0].score_lead(), Some(0.7));
+    }
+
+    #[test] fn errors() {
+        assert!(Analysis::parse("", 9, Format::Leela).expect("empty").moves().is_empty());
+        assert!(Analysis::parse("move D4 visits 3", 9, Format::Leela).is_err());
+        assert!(Analysis::parse("info move Z4 visits 3", 9, Format::Leela).is_err());
+        assert!(Analysis::parse("info visits 3 winrate 5000", 9, Format::Leela).is_err());
+        assert!(Analysis::parse("info visits 3 info move D4 visits 2", 9, Format::Leela).is_err());
+        assert!(Analysis::parse("info move D4 visits 2 info visits 3", 9, Format::Leela).is_err());
+        assert!(Analysis::parse("info move D4 visits lots", 9, Format::Leela).is_err());
+        assert!(Analysis::parse("info move D4 visits 3 ownership 0.5 0.5", 9, Format::Kata).is_err());
+    }
+}
@@ -0,0 +1,347 @@
+// Matches between two GTP engines, as run by `twogtp`.
+//
+// The engines alternate colours from game to game, starting with the first
+// as Black. Every move goes through the crate's own rules engine, so an
+// illegal move loses the game, as does an engine failing to answer. Games
+// which both players pass out are scored by the rules engine, as are games
+// which reach the move limit.
+
+use game::Game;
+use rules::Ruleset;
+use sgf::{Node, Property};
+use sgf::writer::escape;
+use stone::Stone;
+use super::Decision;
+use super::controller::{Controller, Result};
+
+/// One of the engines in a match
+pub struct Entrant {
+    name: String,
+    ctl: Controller,
+    points: f32,
+    black_points: f32,
+    black_games: usize,
+}
+
+impl Entrant {
+    /// Entrant playing with the engine `ctl`, named by its name and version
+    pub fn new(mut ctl: Controller) -> Result<Entrant> {
+        let name = try!(ctl.name());
+        let name = match ctl.version() {
+            Ok(ref v) if !v.is_empty() => format!("{} {}", name, v),
+            _ => name,
+        };
+        Ok(Entrant { name: name, ctl: ctl, points: 0.0, black_points: 0.0, black_games: 0 })
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    /// Points from the games so far; a draw is worth half
+    pub fn points(&self) -> f32 { self.points }
+
+    /// Points from the games played as Black
+    pub fn black_points(&self) -> f32 { self.black_points }
+
+    pub fn black_games(&self) -> usize { self.black_games }
+}
+
+/// How a game ended
+#[derive(Debug, Clone, PartialEq)]
+pub enum End {
+    /// Counted by the rules engine
+    Score,
+    /// The player resigned
+    Resign(Stone),
+    /// The player lost by an illegal move or failing to answer
+    Forfeit(Stone, String),
+}
+
+/// Conditions for the games in a match
+#[derive(Debug, Copy, Clone)]
+pub struct Settings {
+    pub size: usize,
+    pub komi: f32,
+    pub rules: Ruleset,
+    /// Games are scored as they stand after this many moves
+    pub max_moves: usize,
+}
+
+impl Settings {
+    /// Chinese rules with 7.5 komi on a board of `size`
+    pub fn new(size: usize) -> Settings {
+        Settings { size: size, komi: 7.5, rules: Ruleset::chinese(), max_moves: 3 * size * size }
+    }
+}
+
+/// A finished game
+#[derive(Debug, Clone)]
+pub struct Played {
+    game: Game,
+    end: End,
+    black: String,
+    white: String,
+}
+
+impl Played {
+    pub fn game(&self) -> &Game { &self.game }
+    pub fn end(&self) -> &End { &self.end }
+
+    /// Name of the entrant who played Black
+    pub fn black(&self) -> &str { &self.black }
+
+    /// Name of the entrant who played White
+    pub fn white(&self) -> &str { &self.white }
+
+    /// None for a draw
+    pub fn winner(&self) -> Option<Stone> {
+        match self.end {
+            End::Score => self.game.score().winner(),
+            End::Resign(c) | End::Forfeit(c, _) => Some(!c),
+        }
+    }
+
+    /// The result in the form of the SGF RE property
+    pub fn result(&self) -> String {
+        let winner = |c: Stone| if c == Stone::Black { "W" } else { "B" };
+        match self.end {
+            End::Score => self.game.score().to_string(),
+            End::Resign(c) => format!("{}+R", winner(c)),
+            End::Forfeit(c, _) => format!("{}+F", winner(c)),
+        }
+    }
+
+    /// The game record, with the players and the result, and why the loser
+    /// forfeited if they did
+    pub fn to_sgf(&self) -> Node {
+        let mut root = self.game.to_sgf();
+        let prop = |id: &str, v: Vec<u8>| Property::new(id.to_string(), vec![v]);
+
+        root.addprop(prop("PB", escape(&self.black)));
+        root.addprop(prop("PW", escape(&self.white)));
+        root.addprop(prop("RE", escape(&self.result())));
+        if let End::Forfeit(c, ref why) = self.end {
+            root.addprop(prop("C", escape(&format!("{} forfeits: {}", c, why))));
+        }
+        root
+    }
+}
+
+/// A match between two engines
+pub struct Arena {
+    entrants: [Entrant; 2],
+    settings: Settings,
+    games: usize,
+    draws: usize,
+}
+
+impl Arena {
+    /// Match between `a` and `b`, with `a` Black in the first game. Entrants
+    /// with the same name are told apart by " #1" and " #2".
+    pub fn new(mut a: Entrant, mut b: Entrant, settings: Settings) -> Arena {
+        if a.name == b.name {
+            a.name.push_str(" #1");
+            b.name.push_str(" #2");
+        }
+        Arena { entrants: [a, b], settings: settings, games: 0, draws: 0 }
+    }
+
+    pub fn entrants(&self) -> &[Entrant] { &self.entrants }
+    pub fn settings(&self) -> &Settings { &self.settings }
+
+    /// Number of games played so far
+    pub fn games(&self) -> usize { self.games }
+
+    pub fn draws(&self) -> usize { self.draws }
+
+    /// Play the next game and add it to the entrants' points
+    pub fn play(&mut self) -> Played {
+        let (black, white) = if self.games % 2 == 0 { (0, 1) } else { (1, 0) };
+        let (game, end) = {
+            let (first, second) = self.entrants.split_at_mut(1);
+            let mut players = if black == 0 { [&mut first[0], &mut second[0]] } else { [&mut second[0], &mut first[0]] };
+            play(&mut players, &self.settings)
+        };
+
+        let played = Played {
+            game: game,
+            end: end,
+            black: self.entrants[black].name.clone(),
+            white: self.entrants[white].name.clone(),
+        };
+
+        match played.winner() {
+            Some(Stone::Black) => {
+                self.entrants[black].points += 1.0;
+                self.entrants[black].black_points += 1.0;
+            },
+            Some(Stone::White) => self.entrants[white].points += 1.0,
+            None => {
+                self.entrants[black].points += 0.5;
+                self.entrants[black].black_points += 0.5;
+                self.entrants[white].points += 0.5;
+                self.draws += 1;
+            },
+        }
+        self.entrants[black].black_games += 1;
+        self.games += 1;
+
+        played
+    }
+
+    /// Tell both engines to quit
+    pub fn quit(&mut self) {
+        for e in &mut self.entrants {
+            let _ = e.ctl.quit();
+        }
+    }
+}
+
+// Get both engines ready for a new game
+fn setup(players: &mut [&mut Entrant; 2], settings: &Settings) -> ::std::result::Result<(), (usize, String)> {
+    for (i, p) in players.iter_mut().enumerate() {
+        let ctl = &mut p.ctl;
+        try!(ctl.boardsize(settings.size)
+             .and_then(|_| ctl.clear_board())
+             .and_then(|_| ctl.komi(settings.komi))
+             .map_err(|e| (i, e.to_string())));
+    }
+    Ok(())
+}
+
+// Play a game with `players[0]` as Black
+fn play(players: &mut [&mut Entrant; 2], settings: &Settings) -> (Game, End) {
+    let mut game = Game::new(settings.size, settings.rules);
+    game.set_komi(settings.komi);
+    let colours = [Stone::Black, Stone::White];
+
+    if let Err((i, e)) = setup(players, settings) {
+        return (game, End::Forfeit(colours[i], e))
+    }
+
+    while !game.over() && game.history().len() < settings.max_moves {
+        let colour = game.tomove();
+        let (i, j) = if colour == Stone::Black { (0, 1) } else { (1, 0) };
+
+        let mv = match players[i].ctl.genmove(colour) {
+            Ok(Decision::Move(mv)) => mv,
+            Ok(Decision::Resign) => return (game, End::Resign(colour)),
+            Err(e) => return (game, End::Forfeit(colour, format!("genmove failed: {}", e))),
+        };
+        if let Err(e) = game.play(colour, mv) {
+            return (game, End::Forfeit(colour, format!("illegal move {} ({:?})", mv, e)))
+        }
+        if let Err(e) = players[j].ctl.play(colour, mv) {
+            return (game, End::Forfeit(!colour, format!("play {} failed: {}", mv, e)))
+        }
+    }
+
+    (game, End::Score)
+}
+
+/// 95% confidence interval for the fraction of `games` won, given `points`.
+/// The Wilson score interval stays sensible for small numbers of games and
+/// lopsided results, unlike the usual p ± 1.96 standard errors.
+pub fn wilson(points: f32, games: usize) -> (f32, f32) {
+    if games == 0 { return (0.0, 1.0) }
+    let (n, z) = (games as f32, 1.96);
+    let p = points / n;
+
+    let centre = (p + z * z / (2.0 * n)) / (1.0 + z * z / n);
+    let half = z * (p * (1.0 - p) / n + z * z / (4.0 * n * n)).sqrt() / (1.0 + z * z / n);
+    ((centre - half).max(0.0), (centre + half).min(1.0))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+
+    use super::{Arena, End, Entrant, Settings, wilson};
+    use gtp::Controller;
+    use sgf;
+    use stone::Stone::{Black, White};
+
+    // Stand-in engines written in shell: one which always passes, and one
+    // which always plays C3, which is illegal the second time
+    const ENGINE: &'static str = r#"
+while read id cmd args; do
+    case "$cmd" in
+        name) printf '=%s %s\n\n' "$id" "$NAME" ;;
+        version) printf '=%s\n\n' "$id" ;;
+        boardsize|clear_board|komi|play) printf '=%s\n\n' "$id" ;;
+        genmove) printf '=%s %s\n\n' "$id" "$MOVE" ;;
+        quit) printf '=%s\n\n' "$id"; exit 0 ;;
+        *) printf '?%s unknown command\n\n' "$id" ;;
+    esac
+done
+"#;
+
+    fn entrant(name: &str, mv: &str) -> Entrant {
+        let ctl = Controller::spawn(Command::new("sh").arg("-c").arg(ENGINE).env("NAME", name).env("MOVE", mv))
+            .expect("spawn stub engine");
+        Entrant::new(ctl).expect("entrant")
+    }
+
+    #[test] fn games() {
+        let mut arena = Arena::new(entrant("passer", "pass"), entrant("passer", "pass"), Settings::new(5));
+        assert_eq!(arena.entrants()[0].name(), "passer #1");
+        assert_eq!(arena.entrants()[1].name(), "passer #2");
+
+        // Both pass straight away, so White wins by komi, and the colours
+        // alternate
+        for n in 0..4 {
+            let g = arena.play();
+            assert_eq!(*g.end(), End::Score);
+            assert_eq!(g.winner(), Some(White));
+            assert_eq!(g.result(), "W+7.5");
+            let (black, white) = if n % 2 == 0 { ("passer #1", "passer #2") } else { ("passer #2", "passer #1") };
+            assert_eq!((g.black(), g.white()), (black, white));
+        }
+        assert_eq!(arena.games(), 4);
+        assert_eq!(arena.draws(), 0);
+        for e in arena.entrants() {
+            assert_eq!(e.points(), 2.0);
+            assert_eq!(e.black_points(), 0.0);
+            assert_eq!(e.black_games(), 2);
+        }
+        arena.quit();
+    }
+
+    #[test] fn forfeit() {
+        let mut arena = Arena::new(entrant("c3", "C3"), entrant("passer", "pass"), Settings::new(5));
+        let g = arena.play();
+        assert_eq!(g.game().history().len(), 2);
+        match *g.end() {
+            End::Forfeit(Black, ref why) => assert!(why.starts_with("illegal move C3"), "{}", why),
+            ref e => panic!("{:?}", e),
+        }
+        assert_eq!(g.result(), "W+F");
+        assert_eq!(arena.entrants()[1].points(), 1.0);
+
+        // The record has the players, the result and the reason
+        let txt = g.to_sgf().to_string();
+        let coll = sgf::parser(txt.as_bytes()).expect("parse");
+        let root = &coll[0];
+        let text = |id: &str| String::from_utf8(root[id].raw()[0].clone()).unwrap();
+        assert_eq!(text("PB"), "c3");
+        assert_eq!(text("PW"), "passer");
+        assert_eq!(text("RE"), "W+F");
+        assert!(text("C").starts_with("Black forfeits: illegal move C3"));
+        assert_eq!(root[0]["B"].raw()[0], b"cc");
+        assert_eq!(root[0][0]["W"].raw()[0], b"");
+
+        // With colours reversed, it's White's second move which is illegal
+        let g = arena.play();
+        assert_eq!(g.black(), "passer");
+        assert_eq!(*g.end(), End::Forfeit(White, "illegal move C3 (Occupied)".to_string()));
+        arena.quit();
+    }
+
+    #[test] fn interval() {
+        let close = |a: (f32, f32), b: (f32, f32)| (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3;
+        assert!(close(wilson(5.0, 10), (0.237, 0.763)));
+        assert!(close(wilson(10.0, 10), (0.722, 1.0)));
+        assert!(close(wilson(0.0, 10), (0.0, 0.278)));
+        assert!(close(wilson(55.0, 100), (0.452, 0.644)));
+        assert_eq!(wilson(0.0, 0), (0.0, 1.0));
+    }
+}
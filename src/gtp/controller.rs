@@ -0,0 +1,454 @@
+use std::fmt::{self, Display};
+use std::io::{self, BufRead, BufReader, Write};
+use std::mem;
+use std::process::{self, Child, ChildStdin, Stdio};
+use std::result;
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use game::Move;
+use stone::Stone;
+use super::{Decision, parse_score, parse_vertex};
+use super::analysis::{Analysis, Format};
+
+/// Ways talking to an engine can go wrong
+#[derive(Debug)]
+pub enum Error {
+    /// Couldn't start the engine, or write to it
+    Io(io::Error),
+    /// The engine answered with an error message
+    Failure(String),
+    /// The engine's answer didn't make sense
+    Protocol(String),
+    /// The engine took too long to answer
+    Timeout,
+    /// The engine closed its output, most likely because it exited
+    Exited,
+}
+
+impl Display for Error {
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref e) => write!(fmt, "i/o error: {}", e),
+            Error::Failure(ref msg) => write!(fmt, "engine error: {}", msg),
+            Error::Protocol(ref msg) => write!(fmt, "bad response: {}", msg),
+            Error::Timeout => write!(fmt, "timed out"),
+            Error::Exited => write!(fmt, "engine exited"),
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error { Error::Io(e) }
+}
+
+pub type Result<T> = result::Result<T, Error>;
+
+/// The controller side of GTP: runs an engine as a child process and sends
+/// it commands
+///
+/// Every command is sent with an id, so if the engine misses a deadline and
+/// answers late, the stale answer is recognised and skipped. If the deadline
+/// passes partway through an answer, the lines read so far are kept, and the
+/// rest of it is read before the next answer.
+pub struct Controller {
+    child: Child,
+    stdin: ChildStdin,
+    // Lines from the engine's stdout, read by a separate thread so waiting
+    // for them can time out
+    lines: Receiver<String>,
+    // Start of a response which timed out before its blank line
+    partial: Vec<String>,
+    timeout: Option<Duration>,
+    id: u32,
+    size: usize,
+}
+
+impl Controller {
+    /// Start the engine `cmd`, with its stdin and stdout connected to the
+    /// controller
+    pub fn spawn(cmd: &mut process::Command) -> Result<Controller> {
+        let mut child = try!(cmd.stdin(Stdio::piped()).stdout(Stdio::piped()).spawn());
+        let stdin = child.stdin.take().expect("no stdin");
+        let stdout = child.stdout.take().expect("no stdout");
+
+        let (tx, rx) = mpsc::channel();
+        let _ = thread::spawn(move || {
+            for line in BufReader::new(stdout).lines() {
+                match line {
+                    Ok(l) => if tx.send(l).is_err() { break },
+                    Err(_) => break,
+                }
+            }
+        });
+
+        Ok(Controller {
+            child: child,
+            stdin: stdin,
+            lines: rx,
+            partial: Vec::new(),
+            timeout: None,
+            id: 0,
+            size: 19,
+        })
+    }
+
+    /// Give up on commands after this long (None to wait forever)
+    pub fn set_timeout(&mut self, timeout: Option<Duration>) { self.timeout = timeout }
+
+    /// Board size, as last set with `boardsize`
+    pub fn size(&self) -> usize { self.size }
+
+    /// Send a command, returning the engine's answer
+    pub fn send(&mut self, name: &str, args: &[&str]) -> Result<String> {
+        let id = try!(self.write(name, args));
+        self.answer(id)
+    }
+
+    // Send a command, returning its id
+    fn write(&mut self, name: &str, args: &[&str]) -> Result<u32> {
+        self.id += 1;
+        let mut line = format!("{} {}", self.id, name);
+        for a in args {
+            line.push(' ');
+            line.push_str(a);
+        }
+        line.push('\n');
+        try!(self.stdin.write_all(line.as_bytes()));
+        try!(self.stdin.flush());
+        Ok(self.id)
+    }
+
+    // Wait for the answer to command `id`, skipping any to earlier commands
+    fn answer(&mut self, id: u32) -> Result<String> {
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        loop {
+            let (i, result) = try!(self.response(deadline));
+            if i == Some(id) { return result.map_err(Error::Failure) }
+            if i.map_or(true, |i| i > id) {
+                return Err(Error::Protocol(format!("answer for unknown command {:?}", i)))
+            }
+        }
+    }
+
+    // Next line from the engine, without any line ending
+    fn line(&mut self, deadline: Option<Instant>) -> Result<String> {
+        let line = match deadline {
+            Some(d) => {
+                let now = Instant::now();
+                if now >= d { return Err(Error::Timeout) }
+                match self.lines.recv_timeout(d - now) {
+                    Ok(l) => l,
+                    Err(RecvTimeoutError::Timeout) => return Err(Error::Timeout),
+                    Err(RecvTimeoutError::Disconnected) => return Err(Error::Exited),
+                }
+            },
+            None => try!(self.lines.recv().map_err(|_| Error::Exited)),
+        };
+        Ok(line.trim_right_matches('\r').to_string())
+    }
+
+    // Read the next response, and its id, carrying on from any part of it
+    // read before a timeout
+    fn response(&mut self, deadline: Option<Instant>) -> Result<(Option<u32>, result::Result<String, String>)> {
+        loop {
+            let line = try!(self.line(deadline));
+            if line.trim().is_empty() {
+                if self.partial.is_empty() { continue }
+                break
+            }
+            self.partial.push(line);
+        }
+        let mut lines = mem::replace(&mut self.partial, Vec::new());
+
+        let (id, ok, first) = try!(status(&lines[0]));
+        lines[0] = first;
+
+        let text = lines.join("\n");
+        Ok((id, if ok { Ok(text) } else { Err(text) }))
+    }
+
+    pub fn protocol_version(&mut self) -> Result<u32> {
+        let r = try!(self.send("protocol_version", &[]));
+        r.trim().parse().map_err(|_| Error::Protocol(r))
+    }
+
+    pub fn name(&mut self) -> Result<String> { self.send("name", &[]) }
+
+    pub fn version(&mut self) -> Result<String> { self.send("version", &[]) }
+
+    pub fn known_command(&mut self, name: &str) -> Result<bool> {
+        self.send("known_command", &[name]).map(|r| r.trim() == "true")
+    }
+
+    pub fn list_commands(&mut self) -> Result<Vec<String>> {
+        self.send("list_commands", &[]).map(|r| r.lines().map(|l| l.trim().to_string()).collect())
+    }
+
+    pub fn boardsize(&mut self, size: usize) -> Result<()> {
+        try!(self.send("boardsize", &[&size.to_string()]));
+        self.size = size;
+        Ok(())
+    }
+
+    pub fn clear_board(&mut self) -> Result<()> { self.send("clear_board", &[]).map(|_| ()) }
+
+    pub fn komi(&mut self, komi: f32) -> Result<()> { self.send("komi", &[&komi.to_string()]).map(|_| ()) }
+
+    pub fn play(&mut self, colour: Stone, mv: Move) -> Result<()> {
+        self.send("play", &[colour_name(colour), &mv.to_string()]).map(|_| ())
+    }
+
+    /// Ask the engine for a move for `colour`, which it plays on its board
+    pub fn genmove(&mut self, colour: Stone) -> Result<Decision> {
+        let r = try!(self.send("genmove", &[colour_name(colour)]));
+        if r.trim().to_lowercase() == "resign" { return Ok(Decision::Resign) }
+        parse_vertex(r.trim(), self.size).map(Decision::Move).ok_or(Error::Protocol(r))
+    }
+
+    pub fn undo(&mut self) -> Result<()> { self.send("undo", &[]).map(|_| ()) }
+
+    /// The engine's idea of the score, as Black's lead
+    pub fn final_score(&mut self) -> Result<f32> {
+        let r = try!(self.send("final_score", &[]));
+        parse_score(r.trim()).ok_or(Error::Protocol(r))
+    }
+
+    pub fn showboard(&mut self) -> Result<String> { self.send("showboard", &[]) }
+
+    /// Have the engine analyse the position for `colour` for `time`, and
+    /// return the last analysis it gave
+    ///
+    /// The engine reports every `interval` (in centiseconds). The analysis
+    /// stops when the next command is sent, so this sends `protocol_version`
+    /// once the time is up. If the engine gave no analysis at all, there are
+    /// no moves.
+    pub fn analyze(&mut self, format: Format, colour: Stone, interval: u32, time: Duration) -> Result<Analysis> {
+        let mut args = vec![colour_name(colour).to_string(), interval.to_string()];
+        if format == Format::Kata { args.extend(vec!["ownership".to_string(), "true".to_string()]) }
+        let args: Vec<_> = args.iter().map(|a| &a[..]).collect();
+        let id = try!(self.write(format.command(), &args));
+
+        // The first line is the usual status, and the analysis follows.
+        // Before it there may be late answers to earlier commands.
+        let deadline = self.timeout.map(|t| Instant::now() + t);
+        if !self.partial.is_empty() { try!(self.response(deadline)); }
+        loop {
+            let mut first = String::new();
+            while first.trim().is_empty() { first = try!(self.line(deadline)) }
+            match try!(status(&first)) {
+                (Some(i), true, _) if i == id => break,
+                (Some(i), _, _) if i <= id => {
+                    // A failure, or a stale answer: read the rest of it
+                    self.partial.push(first);
+                    let (_, result) = try!(self.response(deadline));
+                    if i == id { return Err(Error::Failure(result.unwrap_or_else(|m| m))) }
+                },
+                _ => return Err(Error::Protocol(first)),
+            }
+        }
+
+        let mut stop = None;
+        let analysis = self.analysis(format, time, &mut stop);
+        if analysis.is_err() {
+            // Stop the analysis and read to the end of it, so that the
+            // answers to later commands aren't taken for analysis
+            if stop.is_none() { stop = self.write("protocol_version", &[]).ok() }
+            let deadline = self.timeout.map(|t| Instant::now() + t);
+            while let Ok(line) = self.line(deadline) {
+                if line.trim().is_empty() { break }
+            }
+        }
+
+        let stopped = stop.map(|s| self.answer(s));
+        let analysis = try!(analysis);
+        if let Some(r) = stopped { try!(r); }
+        Ok(analysis)
+    }
+
+    // Read lines of analysis up to the blank line which ends them, returning
+    // the last. Once `time` is up the analysis is stopped, and `stop` set to
+    // the id of the command which stopped it.
+    fn analysis(&mut self, format: Format, time: Duration, stop: &mut Option<u32>) -> Result<Analysis> {
+        let mut analysis = Analysis::parse("", self.size, format).expect("empty analysis");
+        let mut until = Some(Instant::now() + time);
+        loop {
+            let line = match self.line(until) {
+                Ok(l) => l,
+                Err(Error::Timeout) if stop.is_none() => {
+                    *stop = Some(try!(self.write("protocol_version", &[])));
+                    until = self.timeout.map(|t| Instant::now() + t);
+                    continue
+                },
+                Err(e) => return Err(e),
+            };
+            if line.trim().is_empty() { return Ok(analysis) }
+            if line.trim_left().starts_with("info") {
+                analysis = try!(Analysis::parse(&line, self.size, format).map_err(Error::Protocol));
+            }
+        }
+    }
+
+    /// Tell the engine to quit, and wait for it to exit
+    pub fn quit(&mut self) -> Result<()> {
+        try!(self.send("quit", &[]));
+        try!(self.child.wait());
+        Ok(())
+    }
+}
+
+impl Drop for Controller {
+    // Don't leave the engine running if it hasn't been told to quit
+    fn drop(&mut self) {
+        if let Ok(None) = self.child.try_wait() {
+            let _ = self.child.kill();
+            let _ = self.child.wait();
+        }
+    }
+}
+
+// Split the first line of a response into its id, whether it was a success,
+// and the rest of the line
+fn status(line: &str) -> Result<(Option<u32>, bool, String)> {
+    let ok = match line.chars().next() {
+        Some('=') => true,
+        Some('?') => false,
+        _ => return Err(Error::Protocol(line.to_string())),
+    };
+    let digits: String = line[1..].chars().take_while(|c| c.is_digit(10)).collect();
+    Ok((digits.parse().ok(), ok, line[1 + digits.len()..].trim_left().to_string()))
+}
+
+fn colour_name(colour: Stone) -> &'static str {
+    match colour {
+        Stone::Black => "black",
+        Stone::White => "white",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::process::Command;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::{Controller, Error};
+    use game::Move;
+    use gtp::Decision;
+    use gtp::analysis::Format;
+    use location::Location;
+    use stone::Stone::{Black, White};
+
+    // A stand-in engine written in shell, which answers a few commands with
+    // canned responses
+    const STUB: &'static str = r#"
+while read id cmd args; do
+    case "$cmd" in
+        protocol_version) printf '=%s 2\n\n' "$id" ;;
+        name) printf '=%s stub\n\n' "$id" ;;
+        list_commands) printf '=%s name\nplay\r\nquit\n\n' "$id" ;;
+        boardsize|play) printf '=%s\n\n' "$id" ;;
+        genmove) [ "$args" = white ] && printf '=%s resign\n\n' "$id" || printf '=%s c3\n\n' "$id" ;;
+        undo) printf '?%s cannot undo\n\n' "$id" ;;
+        final_score) printf '=%s W+7.5\n\n' "$id" ;;
+        showboard) printf 'garbage\n\n' ;;
+        sleep) sleep 1; printf '=%s\n\n' "$id" ;;
+        stall) printf '=%s first\n' "$id"; sleep 1; printf 'second\n\n' ;;
+        lz-analyze)
+            if [ "$args" = "white 10" ]; then
+                printf '=%s\ninfo visits 3 winrate 5000\n' "$id"
+                read id cmd args
+                printf '\n=%s 2\n\n' "$id"
+                continue
+            fi
+            printf '=%s\ninfo move C3 visits 2 winrate 5000 prior 2000 order 0 pv C3\n' "$id"
+            printf 'info move C3 visits 9 winrate 5600 prior 2000 order 0 pv C3 D4 info move pass visits 1 winrate 100 prior 10 order 1 pv pass\n'
+            read id cmd args
+            printf '\n=%s 2\n\n' "$id" ;;
+        quit) printf '=%s\n\n' "$id"; exit 0 ;;
+        *) printf '?%s unknown command\n\n' "$id" ;;
+    esac
+done
+"#;
+
+    fn stub() -> Controller {
+        Controller::spawn(Command::new("sh").arg("-c").arg(STUB)).expect("spawn stub engine")
+    }
+
+    #[test] fn commands() {
+        let mut c = stub();
+        assert_eq!(c.protocol_version().expect("protocol_version"), 2);
+        assert_eq!(c.name().expect("name"), "stub");
+        assert_eq!(c.list_commands().expect("list_commands"), vec!["name", "play", "quit"]);
+
+        c.boardsize(9).expect("boardsize");
+        assert_eq!(c.size(), 9);
+        c.play(Black, Move::Pass).expect("play");
+        assert_eq!(c.genmove(Black).expect("genmove"), Decision::Move(Move::Play(Location::from_str("C3").unwrap())));
+        assert_eq!(c.genmove(White).expect("genmove"), Decision::Resign);
+        assert_eq!(c.final_score().expect("final_score"), -7.5);
+
+        c.quit().expect("quit");
+    }
+
+    #[test] fn analyze() {
+        let mut c = stub();
+        c.set_timeout(Some(Duration::from_secs(5)));
+        c.boardsize(9).expect("boardsize");
+
+        let a = c.analyze(Format::Leela, Black, 10, Duration::from_millis(50)).expect("analyze");
+        assert_eq!(a.moves().len(), 2);
+        assert_eq!(a.moves()[0].visits(), 9);
+        assert_eq!(a.moves()[0].pv(), &[Move::Play(Location::from_str("C3").unwrap()), Move::Play(Location::from_str("D4").unwrap())]);
+        assert_eq!(a.moves()[1].mv(), Move::Pass);
+
+        // Back in step afterwards
+        assert_eq!(c.name().expect("name"), "stub");
+        c.quit().expect("quit");
+    }
+
+    #[test] fn errors() {
+        let mut c = stub();
+        match c.undo() { Err(Error::Failure(ref m)) if m == "cannot undo" => (), r => panic!("undo gave {:?}", r) }
+        match c.send("fly", &[]) { Err(Error::Failure(ref m)) if m == "unknown command" => (), r => panic!("fly gave {:?}", r) }
+        match c.showboard() { Err(Error::Protocol(_)) => (), r => panic!("showboard gave {:?}", r) }
+
+        // A late answer is skipped when it finally arrives
+        c.set_timeout(Some(Duration::from_millis(100)));
+        match c.send("sleep", &[]) { Err(Error::Timeout) => (), r => panic!("sleep gave {:?}", r) }
+        c.set_timeout(Some(Duration::from_secs(5)));
+        assert_eq!(c.name().expect("name"), "stub");
+
+        // So is one which stops halfway
+        c.set_timeout(Some(Duration::from_millis(100)));
+        match c.send("stall", &[]) { Err(Error::Timeout) => (), r => panic!("stall gave {:?}", r) }
+        c.set_timeout(Some(Duration::from_secs(5)));
+        assert_eq!(c.name().expect("name"), "stub");
+
+        // and before analysis
+        c.set_timeout(Some(Duration::from_millis(100)));
+        match c.send("sleep", &[]) { Err(Error::Timeout) => (), r => panic!("sleep gave {:?}", r) }
+        c.set_timeout(Some(Duration::from_secs(5)));
+        let a = c.analyze(Format::Leela, Black, 10, Duration::from_millis(50)).expect("analyze after sleep");
+        assert_eq!(a.moves().len(), 2);
+        assert_eq!(c.name().expect("name"), "stub");
+
+        // Analysis which can't be read is stopped, leaving the engine ready
+        // for the next command
+        match c.analyze(Format::Leela, White, 10, Duration::from_millis(50)) {
+            Err(Error::Protocol(_)) => (),
+            r => panic!("bad analysis gave {:?}", r),
+        }
+        assert_eq!(c.name().expect("name"), "stub");
+
+        match c.analyze(Format::Kata, Black, 10, Duration::from_millis(50)) {
+            Err(Error::Failure(ref m)) if m == "unknown command" => (),
+            r => panic!("kata-analyze gave {:?}", r),
+        }
+
+        c.quit().expect("quit");
+        match c.name() { Err(Error::Io(_)) | Err(Error::Exited) => (), r => panic!("name after quit gave {:?}", r) }
+        assert!(Controller::spawn(&mut Command::new("/nonexistent/engine")).is_err());
+    }
+}
@@ -0,0 +1,439 @@
+use std::fs::File;
+use std::io::{self, BufRead, Read, Write};
+use std::time::{Duration, Instant};
+
+use clock::{Clock, Remaining, TimeControl};
+use game::{self, Game};
+use location::Location;
+use player::Player;
+use rules::Ruleset;
+use sgf;
+use stone::Stone;
+use super::{Command, MAX_SIZE, parse_colour, parse_vertex, response};
+
+const COMMANDS: &'static [&'static str] = &[
+    "boardsize",
+    "clear_board",
+    "final_score",
+    "genmove",
+    "known_command",
+    "komi",
+    "list_commands",
+    "loadsgf",
+    "name",
+    "play",
+    "printsgf",
+    "protocol_version",
+    "quit",
+    "showboard",
+    "time_left",
+    "time_settings",
+    "undo",
+    "version",
+];
+
+/// The engine side of GTP: keeps the game the controller sets up, and asks a
+/// `Player` for moves, giving it time according to the clock
+pub struct Engine {
+    game: Game,
+    clock: Clock,
+    // The clock as it stood after each move of the game
+    clocks: Vec<Clock>,
+    rules: Ruleset,
+    player: Box<Player>,
+    quit: bool,
+}
+
+impl Engine {
+    /// Engine playing under `rules` on a 19x19 board until told otherwise
+    pub fn new(rules: Ruleset, player: Box<Player>) -> Engine {
+        Engine {
+            game: Game::new(19, rules),
+            clock: Clock::new(TimeControl::Unlimited),
+            clocks: Vec::new(),
+            rules: rules,
+            player: player,
+            quit: false,
+        }
+    }
+
+    pub fn game(&self) -> &Game { &self.game }
+
+    pub fn clock(&self) -> &Clock { &self.clock }
+
+    /// Whether the controller has sent `quit`
+    pub fn quit(&self) -> bool { self.quit }
+
+    /// Handle a line of input, returning the response, or None if the line
+    /// had no command in it
+    pub fn handle(&mut self, line: &str) -> Option<String> {
+        Command::parse(line).map(|cmd| {
+            let result = self.command(&cmd.name, &cmd.args);
+            response(cmd.id, result)
+        })
+    }
+
+    /// Answer commands from `input` until it runs out or the controller quits
+    pub fn run<R: BufRead, W: Write>(&mut self, input: R, mut output: W) -> io::Result<()> {
+        for line in input.lines() {
+            if let Some(r) = self.handle(&try!(line)) {
+                try!(output.write_all(r.as_bytes()));
+                try!(output.flush());
+            }
+            if self.quit { break }
+        }
+        Ok(())
+    }
+
+    fn command(&mut self, name: &str, args: &[String]) -> Result<String, String> {
+        let arg = |i: usize| args.get(i).map(|s| &s[..]).ok_or_else(|| "syntax error".to_string());
+        let colour = |i: usize| arg(i).and_then(|a| parse_colour(a).ok_or_else(|| "syntax error".to_string()));
+        let number = |i: usize| arg(i).and_then(|a| a.parse::<u64>().map_err(|_| "syntax error".to_string()));
+        let count = |i: usize| arg(i).and_then(|a| a.parse::<u32>().map_err(|_| "syntax error".to_string()));
+
+        match name {
+            "protocol_version" => Ok("2".to_string()),
+            "name" => Ok("go".to_string()),
+            "version" => Ok(env!("CARGO_PKG_VERSION").to_string()),
+            "known_command" => Ok(COMMANDS.contains(&try!(arg(0))).to_string()),
+            "list_commands" => Ok(COMMANDS.join("\n")),
+            "quit" => { self.quit = true; Ok(String::new()) },
+
+            "boardsize" => {
+                let size: usize = try!(try!(arg(0)).parse().map_err(|_| "syntax error".to_string()));
+                if size < 1 || size > MAX_SIZE { return Err("unacceptable size".to_string()) }
+                self.clear(size);
+                Ok(String::new())
+            },
+            "clear_board" => {
+                let size = self.game.size();
+                self.clear(size);
+                Ok(String::new())
+            },
+            "komi" => {
+                let komi = try!(try!(arg(0)).parse().map_err(|_| "syntax error".to_string()));
+                self.game.set_komi(komi);
+                Ok(String::new())
+            },
+
+            "play" => {
+                let colour = try!(colour(0));
+                let mv = try!(parse_vertex(try!(arg(1)), self.game.size()).ok_or_else(|| "syntax error".to_string()));
+                try!(self.game.play(colour, mv).map_err(|_| "illegal move".to_string()));
+                self.clocks.push(self.clock.clone());
+                Ok(String::new())
+            },
+            "genmove" => {
+                let colour = try!(colour(0));
+                self.player.set_move_time(self.clock.budget(colour));
+                let start = Instant::now();
+                let mv = self.player.genmove(&self.game, colour);
+                self.clock.charge(colour, start.elapsed());
+                try!(self.game.play(colour, mv).map_err(|_| "player chose an illegal move".to_string()));
+                self.clocks.push(self.clock.clone());
+                Ok(mv.to_string())
+            },
+
+            "time_settings" => {
+                let (main, period, stones) = (try!(number(0)), try!(number(1)), try!(count(2)));
+                self.clock = Clock::new(TimeControl::from_gtp(main, period, stones));
+                Ok(String::new())
+            },
+            "time_left" => {
+                let colour = try!(colour(0));
+                let (time, stones) = (try!(number(1)), try!(count(2)));
+                self.clock.set_remaining(colour, Remaining { time: Duration::from_secs(time), periods: stones });
+                Ok(String::new())
+            },
+
+            "undo" => {
+                if !self.game.undo() { return Err("cannot undo".to_string()) }
+                let _ = self.clocks.pop();
+                Ok(String::new())
+            },
+
+            "loadsgf" => {
+                let moves = match args.get(1) {
+                    Some(m) => Some(try!(m.parse().map_err(|_| "syntax error".to_string()))),
+                    None => None,
+                };
+                let (game, clocks) = try!(load(try!(arg(0)), moves, self.rules));
+                self.game = game;
+                self.clock = clocks.last().expect("no clock").clone();
+                self.clocks = clocks[1..].to_vec();
+                Ok(String::new())
+            },
+            "printsgf" => {
+                let clocks = &self.clocks;
+                let mut root = self.game.to_sgf_with(|i, colour, node| {
+                    if let Some(c) = clocks.get(i) { c.to_sgf(colour, node) }
+                });
+                self.clock.control().to_sgf(&mut root);
+                let txt = root.to_string();
+                match args.first() {
+                    Some(path) => File::create(path).and_then(|mut f| f.write_all(txt.as_bytes()))
+                        .map(|_| String::new())
+                        .map_err(|_| "cannot write file".to_string()),
+                    None => Ok(txt),
+                }
+            },
+
+            "showboard" => Ok(self.showboard()),
+            "final_score" => Ok(self.game.score().to_string()),
+
+            _ => Err("unknown command".to_string()),
+        }
+    }
+
+    // Start a new game on an empty board, keeping the komi and time control
+    fn clear(&mut self, size: usize) {
+        let komi = self.game.komi();
+        self.game = Game::new(size, self.rules);
+        self.game.set_komi(komi);
+        self.clock = Clock::new(self.clock.control());
+        self.clocks.clear();
+    }
+
+    // The board with coordinates around it, starting on a new line so it
+    // doesn't end up after the "="
+    fn showboard(&self) -> String {
+        let board = self.game.board();
+        let size = board.size();
+        let letters: String = (0..size).map(|c| format!(" {}", Location::new(c, 0).to_string().remove(0))).collect();
+
+        let mut s = format!("\n  {}\n", letters);
+        for row in (0..size).rev() {
+            s.push_str(&format!("{:2}", row + 1));
+            for col in 0..size {
+                s.push_str(match board.get(Location::new(col, row)) {
+                    Some(Stone::Black) => " X",
+                    Some(Stone::White) => " O",
+                    None => " .",
+                });
+            }
+            s.push_str(&format!(" {}\n", row + 1));
+        }
+        s.push_str(&format!("  {}\n", letters));
+        s.push_str(&format!("Black has captured {} stones, White {}; {} to move",
+                            self.game.captures(Stone::Black), self.game.captures(Stone::White), self.game.tomove()));
+        s
+    }
+}
+
+// The game in the SGF file at `path`, following the main line up to move
+// number `moves` (counting from 1), which is left for the player to make,
+// and the clock as of the last move played. Games which don't give their
+// rules are played under `rules`.
+// Load a game, and the clock at the start and after each move
+fn load(path: &str, moves: Option<usize>, rules: Ruleset) -> Result<(Game, Vec<Clock>), String> {
+    let mut txt = Vec::new();
+    try!(File::open(path).and_then(|mut f| f.read_to_end(&mut txt)).map_err(|_| "cannot open file".to_string()));
+    let coll = try!(sgf::parser(&txt).map_err(|_| "cannot read file".to_string()));
+    let root = try!(coll.first().ok_or_else(|| "cannot read file".to_string()));
+
+    let mut game = try!(Game::from_sgf_with_rules(root, rules).map_err(|_| "cannot read file".to_string()));
+    let size = game.size();
+    let mut clock = Clock::new(try!(TimeControl::from_sgf(root).map_err(|_| "cannot read file".to_string())));
+    if size < 1 || size > MAX_SIZE { return Err("unacceptable size".to_string()) }
+
+    let mut clocks = vec![clock.clone()];
+    let mut node = root;
+    let mut played = 0;
+    loop {
+        if let Some(m) = game::sgf_move(node, size) {
+            let (colour, mv) = try!(m.map_err(|_| "cannot read file".to_string()));
+            game.set_tomove(colour);
+            if moves.map_or(false, |m| played + 1 >= m) { break }
+            try!(game.play(colour, mv).map_err(|_| format!("illegal move {} {}", colour, mv)));
+            played += 1;
+            try!(clock.update_sgf(node).map_err(|_| "cannot read file".to_string()));
+            clocks.push(clock.clone());
+        }
+
+        if node.len() == 0 { break }
+        node = &node[0];
+        try!(game.setup_sgf(node).map_err(|_| "cannot read file".to_string()));
+    }
+
+    Ok((game, clocks))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::{Read, Write};
+    use std::path::PathBuf;
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::Engine;
+    use clock::{Remaining, TimeControl};
+    use game::{Game, Move};
+    use location::Location;
+    use mcts::Mcts;
+    use player::{Player, RandomPlayer};
+    use rules::Ruleset;
+    use stone::Stone;
+
+    // Always plays at the same point, whether it's free or not
+    struct Stubborn(Move);
+
+    impl Player for Stubborn {
+        fn genmove(&mut self, _game: &Game, _colour: Stone) -> Move { self.0 }
+    }
+
+    // Deleted when the test ends, whether it passed or not
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) { let _ = fs::remove_file(&self.0); }
+    }
+
+    fn engine() -> Engine { Engine::new(Ruleset::chinese(), Box::new(RandomPlayer::with_seed(1))) }
+
+    fn send(e: &mut Engine, line: &str) -> String { e.handle(line).expect("no response") }
+
+    #[test] fn protocol() {
+        let mut e = engine();
+        assert_eq!(send(&mut e, "protocol_version"), "= 2\n\n");
+        assert_eq!(send(&mut e, "1 name"), "=1 go\n\n");
+        assert_eq!(send(&mut e, "2 known_command genmove"), "=2 true\n\n");
+        assert_eq!(send(&mut e, "known_command fly"), "= false\n\n");
+        assert_eq!(send(&mut e, "fly"), "? unknown command\n\n");
+        assert!(send(&mut e, "list_commands").contains("\nfinal_score\n"));
+        assert_eq!(e.handle("# nothing"), None);
+
+        assert!(!e.quit());
+        assert_eq!(send(&mut e, "quit"), "= \n\n");
+        assert!(e.quit());
+    }
+
+    #[test] fn game() {
+        let mut e = engine();
+        assert_eq!(send(&mut e, "boardsize 7"), "= \n\n");
+        assert_eq!(send(&mut e, "boardsize 26"), "? unacceptable size\n\n");
+        assert_eq!(send(&mut e, "komi 0.5"), "= \n\n");
+        assert_eq!(send(&mut e, "clear_board"), "= \n\n");
+        assert_eq!(e.game().size(), 7);
+        assert_eq!(e.game().komi(), 0.5);
+
+        assert_eq!(send(&mut e, "play b D4"), "= \n\n");
+        assert_eq!(send(&mut e, "play w d4"), "? illegal move\n\n");
+        assert_eq!(send(&mut e, "play w H1"), "? syntax error\n\n");
+        assert_eq!(send(&mut e, "play green A1"), "? syntax error\n\n");
+        assert_eq!(e.game().board().get(Location::from_str("D4").unwrap()), Some(Stone::Black));
+
+        let board = send(&mut e, "showboard");
+        assert!(board.starts_with("= \n   A B C D E F G\n"));
+        assert!(board.contains("\n 4 . . . X . . . 4\n"));
+
+        let r = send(&mut e, "genmove white");
+        let mv = e.game().history().last().expect("no move").mv();
+        assert_eq!(r, format!("= {}\n\n", mv));
+        assert!(mv != Move::Pass);
+
+        assert_eq!(send(&mut e, "undo"), "= \n\n");
+        assert_eq!(send(&mut e, "undo"), "= \n\n");
+        assert_eq!(send(&mut e, "undo"), "? cannot undo\n\n");
+
+        assert_eq!(send(&mut e, "play b pass"), "= \n\n");
+        assert_eq!(send(&mut e, "play w pass"), "= \n\n");
+        assert_eq!(send(&mut e, "final_score"), "= W+0.5\n\n");
+    }
+
+    #[test] fn illegal_genmove() {
+        let d4 = Move::Play(Location::from_str("D4").unwrap());
+        let mut e = Engine::new(Ruleset::chinese(), Box::new(Stubborn(d4)));
+        assert_eq!(send(&mut e, "genmove b"), "= D4\n\n");
+        assert_eq!(send(&mut e, "genmove w"), "? player chose an illegal move\n\n");
+        assert_eq!(e.game().history().len(), 1);
+    }
+
+    #[test] fn sgf() {
+        let file = TempFile(env::temp_dir().join(format!("gtp-loadsgf-{}.sgf", ::std::process::id())));
+        let path = file.0.to_str().expect("path");
+        File::create(path).and_then(|mut f| f.write_all(b"(;SZ[9]KM[6.5]AB[cc]AW[gg];W[ee];B[dd](;W[]) (;W[aa]))"))
+            .expect("write sgf");
+
+        let mut e = engine();
+        assert_eq!(send(&mut e, &format!("loadsgf {}", path)), "= \n\n");
+        assert_eq!(e.game().size(), 9);
+        assert_eq!(e.game().komi(), 6.5);
+        assert_eq!(*e.game().rules(), Ruleset::chinese());
+        assert_eq!(e.game().history().len(), 3);
+        assert_eq!(e.game().board().get(Location::from_str("C7").unwrap()), Some(Stone::Black));
+        assert_eq!(e.game().tomove(), Stone::Black);
+
+        // Up to but not including the second move
+        assert_eq!(send(&mut e, &format!("loadsgf {} 2", path)), "= \n\n");
+        assert_eq!(e.game().history().len(), 1);
+        assert_eq!(e.game().tomove(), Stone::Black);
+        assert_eq!(send(&mut e, &format!("loadsgf {} 1", path)), "= \n\n");
+        assert_eq!(e.game().history().len(), 0);
+        assert_eq!(e.game().tomove(), Stone::White);
+        assert_eq!(e.game().board().get(Location::from_str("G3").unwrap()), Some(Stone::White));
+
+        assert_eq!(send(&mut e, "loadsgf /nonexistent/file.sgf"), "? cannot open file\n\n");
+        assert_eq!(send(&mut e, &format!("loadsgf {} x", path)), "? syntax error\n\n");
+
+        assert_eq!(send(&mut e, "play w E5"), "= \n\n");
+        assert_eq!(send(&mut e, "printsgf"),
+                   "= (;AB[cc]AW[gg]FF[4]GM[1]KM[6.5]PL[W]RU[Chinese]SZ[9];W[ee])\n\n");
+        assert_eq!(send(&mut e, &format!("printsgf {}", path)), "= \n\n");
+        let mut txt = String::new();
+        File::open(path).and_then(|mut f| f.read_to_string(&mut txt)).expect("read sgf");
+        assert_eq!(txt, "(;AB[cc]AW[gg]FF[4]GM[1]KM[6.5]PL[W]RU[Chinese]SZ[9];W[ee])");
+
+        // Rules given in the file win over the engine's
+        File::create(path).and_then(|mut f| f.write_all(b"(;SZ[9]RU[Japanese];B[ee])")).expect("write sgf");
+        assert_eq!(send(&mut e, &format!("loadsgf {}", path)), "= \n\n");
+        assert_eq!(*e.game().rules(), Ruleset::japanese());
+    }
+
+    #[test] fn time() {
+        let mut e = engine();
+        assert_eq!(e.clock().control(), TimeControl::Unlimited);
+        assert_eq!(send(&mut e, "time_settings 600 30 5"), "= \n\n");
+        let canadian = TimeControl::Canadian { main: Duration::from_secs(600), period: Duration::from_secs(30), stones: 5 };
+        assert_eq!(e.clock().control(), canadian);
+        assert_eq!(send(&mut e, "time_settings 600 thirty 5"), "? syntax error\n\n");
+
+        assert_eq!(send(&mut e, "time_left w 25 3"), "= \n\n");
+        assert_eq!(e.clock().remaining(Stone::White), Remaining { time: Duration::from_secs(25), periods: 3 });
+        assert_eq!(send(&mut e, "time_left b 60"), "? syntax error\n\n");
+        assert_eq!(send(&mut e, "time_left b 60 4294967296"), "? syntax error\n\n");
+        assert_eq!(send(&mut e, "time_settings 600 30 4294967301"), "? syntax error\n\n");
+
+        // The player's time comes off the clock
+        let _ = send(&mut e, "genmove b");
+        assert!(e.clock().remaining(Stone::Black).time < Duration::from_secs(600));
+
+        // and what's left after each move goes in the SGF
+        assert_eq!(send(&mut e, "play w pass"), "= \n\n");
+        let sgf = send(&mut e, "printsgf");
+        assert!(sgf.contains("OT[5/30 Canadian]") && sgf.contains(";B[") && sgf.contains("BL["), "{}", sgf);
+        assert!(sgf.ends_with(";OW[3]W[]WL[25.000])\n\n"), "{}", sgf);
+        assert_eq!(send(&mut e, "undo"), "= \n\n");
+        assert!(!send(&mut e, "printsgf").contains("WL["));
+
+        // A new game starts the clocks again
+        assert_eq!(send(&mut e, "clear_board"), "= \n\n");
+        assert_eq!(e.clock().remaining(Stone::White), Remaining { time: Duration::from_secs(600), periods: 0 });
+
+        assert_eq!(send(&mut e, "boardsize 5"), "= \n\n");
+        assert_eq!(send(&mut e, "printsgf"), "= (;FF[4]GM[1]KM[7.5]OT[5/30 Canadian]RU[Chinese]SZ[5]TM[600])\n\n");
+    }
+
+    #[test] fn player_time() {
+        // Without time_settings the player searches for as long as it was
+        // set up to
+        let mut m = Mcts::with_seed(Game::new(5, Ruleset::chinese()), 1);
+        m.set_playouts(None);
+        m.set_time(Some(Duration::from_millis(100)));
+        let mut e = Engine::new(Ruleset::chinese(), Box::new(m));
+        assert_eq!(send(&mut e, "boardsize 5"), "= \n\n");
+        let _ = send(&mut e, "genmove b");
+        assert!(e.game().history().last().expect("no move").mv() != Move::Pass);
+    }
+}
@@ -0,0 +1,157 @@
+// The Go Text Protocol, version 2, which Go programs use to talk to graphical
+// front ends and to each other.
+//
+// A controller sends one command per line, optionally preceded by a numeric
+// id, and the engine answers each with "=" for success or "?" for failure,
+// the id if there was one, and the result or error message, followed by a
+// blank line. See http://www.lysator.liu.se/~gunnar/gtp/ for the details.
+//
+// `Engine` is the engine's end, and `Controller` the controller's, for
+// running other programs, which can also collect their analysis. `Arena`
+// runs matches between two of them.
+
+use std::str::FromStr;
+
+use game::Move;
+use location::Location;
+use stone::Stone;
+
+pub mod analysis;
+pub mod arena;
+pub mod controller;
+mod engine;
+
+pub use self::analysis::Analysis;
+pub use self::arena::Arena;
+pub use self::controller::Controller;
+pub use self::engine::Engine;
+
+/// Largest board size GTP has coordinates for
+pub const MAX_SIZE: usize = 25;
+
+/// An engine's answer to `genmove`
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Decision {
+    Move(Move),
+    Resign,
+}
+
+/// A command from the controller
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Command {
+    pub id: Option<u32>,
+    pub name: String,
+    pub args: Vec<String>,
+}
+
+impl Command {
+    /// Parse one line of input, returning None for lines with nothing but
+    /// whitespace and comments
+    pub fn parse(line: &str) -> Option<Command> {
+        // Control characters are dropped, except for tabs, which are spaces,
+        // and everything after a '#' is a comment
+        let line: String = line.chars()
+            .take_while(|&c| c != '#')
+            .filter(|&c| c == '\t' || !c.is_control())
+            .map(|c| if c == '\t' { ' ' } else { c })
+            .collect();
+
+        let mut words = line.split_whitespace().peekable();
+        let id = words.peek().and_then(|w| w.parse().ok());
+        if id.is_some() { let _ = words.next(); }
+
+        words.next().map(|name| Command {
+            id: id,
+            name: name.to_string(),
+            args: words.map(|w| w.to_string()).collect(),
+        })
+    }
+}
+
+/// Format the response to a command with `id`
+pub fn response(id: Option<u32>, result: Result<String, String>) -> String {
+    let id = id.map_or(String::new(), |id| id.to_string());
+    match result {
+        Ok(r) => format!("={} {}\n\n", id, r),
+        Err(e) => format!("?{} {}\n\n", id, e),
+    }
+}
+
+/// Parse a colour: "b", "black", "w" or "white", in any case
+pub fn parse_colour(s: &str) -> Option<Stone> {
+    match &s.to_lowercase()[..] {
+        "b" | "black" => Some(Stone::Black),
+        "w" | "white" => Some(Stone::White),
+        _ => None,
+    }
+}
+
+/// Parse a vertex such as "D4", or "pass", which must be on a board of `size`
+pub fn parse_vertex(s: &str, size: usize) -> Option<Move> {
+    if s.to_lowercase() == "pass" { return Some(Move::Pass) }
+
+    match Location::from_str(s) {
+        Ok(l) if l.col() < size && l.row() < size => Some(Move::Play(l)),
+        _ => None,
+    }
+}
+
+/// Parse a score in the form `final_score` gives, such as "B+3.5", "W+12"
+/// or "0", as Black's lead
+pub fn parse_score(s: &str) -> Option<f32> {
+    let s = s.to_uppercase();
+    if s == "0" { return Some(0.0) }
+
+    let margin = s.get(2..).and_then(|m| m.parse::<f32>().ok());
+    if s.starts_with("B+") {
+        margin
+    } else if s.starts_with("W+") {
+        margin.map(|m| -m)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::*;
+    use game::Move;
+    use location::Location;
+    use stone::Stone;
+
+    #[test] fn commands() {
+        assert_eq!(Command::parse("  # just a comment"), None);
+        assert_eq!(Command::parse("\t\r"), None);
+        assert_eq!(Command::parse("7"), None);
+        assert_eq!(Command::parse("play\tB D4 # there"),
+                   Some(Command { id: None, name: "play".into(), args: vec!["B".into(), "D4".into()] }));
+        assert_eq!(Command::parse("12 genmove w\r"),
+                   Some(Command { id: Some(12), name: "genmove".into(), args: vec!["w".into()] }));
+    }
+
+    #[test] fn responses() {
+        assert_eq!(response(None, Ok("2".into())), "= 2\n\n");
+        assert_eq!(response(Some(3), Ok(String::new())), "=3 \n\n");
+        assert_eq!(response(Some(4), Err("illegal move".into())), "?4 illegal move\n\n");
+    }
+
+    #[test] fn values() {
+        assert_eq!(parse_colour("B"), Some(Stone::Black));
+        assert_eq!(parse_colour("white"), Some(Stone::White));
+        assert_eq!(parse_colour("x"), None);
+
+        assert_eq!(parse_vertex("PASS", 9), Some(Move::Pass));
+        assert_eq!(parse_vertex("j9", 9), Some(Move::Play(Location::from_str("J9").unwrap())));
+        assert_eq!(parse_vertex("K9", 9), None);
+        assert_eq!(parse_vertex("A10", 9), None);
+        assert_eq!(parse_vertex("I3", 9), None);
+
+        assert_eq!(parse_score("B+3.5"), Some(3.5));
+        assert_eq!(parse_score("w+12"), Some(-12.0));
+        assert_eq!(parse_score("0"), Some(0.0));
+        assert_eq!(parse_score("W+R"), None);
+        assert_eq!(parse_score("B"), None);
+    }
+}
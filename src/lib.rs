@@ -6,7 +6,26 @@ pub mod board;
 pub mod stone;
 pub mod location;
 pub mod group;
+pub mod rules;
+pub mod game;
+pub mod tactics;
+pub mod semeai;
+pub mod graph;
+pub mod safety;
+pub mod eyeshape;
+pub mod tsumego;
+pub mod solve;
+pub mod pattern;
+pub mod features;
+pub mod nn;
+pub mod shape;
+pub mod player;
+pub mod policy;
+pub mod mcts;
 pub mod sgf;
+pub mod clock;
+pub mod gtp;
 
 mod one;
 mod accum;
+mod rng;
@@ -23,6 +23,10 @@ impl Location {
     pub fn neighbours(&self) -> Neighbours {
         Neighbours::new(self)
     }
+
+    pub fn diagonals(&self) -> Diagonals {
+        Diagonals::new(self)
+    }
 }
 
 // Convert into bitset
@@ -80,7 +84,7 @@ impl FromStr for Location {
         let col = match si.next() {
             Some(c@'A'...'H') | Some(c@'a'...'h') =>
                 c.to_lowercase().next().unwrap() as usize - 'a' as usize,
-            Some(c@'J'...'T') | Some(c@'j'...'t') =>
+            Some(c@'J'...'Z') | Some(c@'j'...'z') =>
                 c.to_lowercase().next().unwrap() as usize - 'j' as usize + 8,
             _ => return Err("bad col"),
         };
@@ -165,6 +169,38 @@ impl Iterator for Neighbours {
     }
 }
 
+pub struct Diagonals {
+    loc: Location,
+    n: usize,
+}
+
+impl Diagonals {
+    pub fn new(loc: &Location) -> Self {
+        Diagonals { loc: *loc, n: 0 }
+    }
+}
+
+impl Iterator for Diagonals {
+    type Item = Location;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (c, r) = (self.loc.col, self.loc.row);
+        loop {
+            let n = self.n;
+            self.n += 1;
+
+            match n {
+                0 => if c > 0 && r > 0 { return Some(Location::new(c - 1, r - 1)) },
+                1 => if r > 0 { return Some(Location::new(c + 1, r - 1)) },
+                2 => if c > 0 { return Some(Location::new(c - 1, r + 1)) },
+                3 => return Some(Location::new(c + 1, r + 1)),
+
+                _ => return None,
+            };
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -188,6 +224,15 @@ mod tests {
                          Location::new(1,0), Location::new(1,2)]);
     }
 
+    #[test] fn diagonals() {
+        assert_eq!(Location::new(0,0).diagonals().collect::<Vec<_>>(),
+                    vec![Location::new(1,1)]);
+
+        assert_eq!(Location::new(1,1).diagonals().collect::<Vec<_>>(),
+                    vec![Location::new(0,0), Location::new(2,0),
+                         Location::new(0,2), Location::new(2,2)]);
+    }
+
     #[test] fn parseloc() {
         assert_eq!(FromStr::from_str("a1"), Ok(Location::new(0, 0)));
         assert_eq!(FromStr::from_str("h1"), Ok(Location::new(7, 0)));
@@ -202,5 +247,7 @@ mod tests {
         assert_eq!(FromStr::from_str("T1"), Ok(Location::new(18, 0)));
         assert_eq!(FromStr::from_str("A19"), Ok(Location::new(0, 18)));
         assert_eq!(FromStr::from_str("T19"), Ok(Location::new(18, 18)));
+        assert_eq!(FromStr::from_str("Z25"), Ok(Location::new(24, 24)));
+        assert!(Location::from_str("I1").is_err());
     }
 }
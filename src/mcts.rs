@@ -0,0 +1,615 @@
+// Monte Carlo tree search.
+//
+// UCT with RAVE: each node keeps the results of the playouts through it, and
+// also "all moves as first" (AMAF) results, counting a playout for every
+// move which the same player made later in it. Early on a node's value leans
+// on the AMAF results, which build up much faster, and as real visits
+// accumulate it moves over to them. Playouts are played by `RandomPlayer`, or
+// by `PatternPlayer` when the search is given a table of pattern weights.
+//
+// Given a neural network, the search uses it instead of playouts: the value
+// of a new leaf is the network's evaluation of it, and the network's move
+// probabilities are added to the exploration term so the likely moves are
+// tried first.
+//
+// Several threads can search the same tree. Each holds the tree's lock while
+// walking down it and while recording a result, but not during the playout
+// itself, which is where nearly all the time goes. A visit is counted as soon
+// as a thread passes through a node, and the win only added when its playout
+// finishes; until then the node looks like it lost (a "virtual loss"), which
+// steers the other threads towards different lines.
+
+use std::cmp::max;
+use std::f32;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use game::{Game, Move};
+use location::Location;
+use nn::{Evaluation, Network};
+use player::{Player, RandomPlayer};
+use policy::{PatternPlayer, Weights};
+use rng::Rng;
+use stone::Stone;
+
+#[derive(Debug, Clone)]
+struct Node {
+    mv: Move,
+    // Player who made `mv`
+    colour: Stone,
+    children: Vec<usize>,
+    expanded: bool,
+    visits: u32,
+    wins: f32,
+    amaf_visits: u32,
+    amaf_wins: f32,
+    // Probability the network gives the move
+    prior: f32,
+}
+
+impl Node {
+    fn new(mv: Move, colour: Stone) -> Node {
+        Node {
+            mv: mv,
+            colour: colour,
+            children: Vec::new(),
+            expanded: false,
+            visits: 0,
+            wins: 0.0,
+            amaf_visits: 0,
+            amaf_wins: 0.0,
+            prior: 0.0,
+        }
+    }
+}
+
+// Search parameters, copied into each thread
+#[derive(Debug, Copy, Clone)]
+struct Settings {
+    exploration: f32,
+    rave: f32,
+    max_moves: usize,
+    prior: f32,
+    // Value of a move before it has been tried
+    urgency: f32,
+}
+
+// How a thread finds the value of a leaf
+enum Evaluator {
+    Playout(Box<Player + Send>),
+    Network(Arc<Network>),
+}
+
+// The nodes, stored in one vector and referring to each other by index
+#[derive(Debug)]
+struct Tree {
+    nodes: Vec<Node>,
+    rng: Rng,
+}
+
+/// Search results for one of the moves at the root
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct MoveStats {
+    mv: Move,
+    visits: usize,
+    winrate: f32,
+}
+
+impl MoveStats {
+    pub fn mv(&self) -> Move { self.mv }
+    pub fn visits(&self) -> usize { self.visits }
+
+    /// Fraction of the playouts through the move won by the side to move
+    pub fn winrate(&self) -> f32 { self.winrate }
+}
+
+#[derive(Debug)]
+pub struct Mcts {
+    game: Game,
+    tree: Arc<Mutex<Tree>>,
+    // Seeds the playout players for each search
+    rng: Rng,
+    policy: Option<Arc<Weights>>,
+    network: Option<Arc<Network>>,
+    settings: Settings,
+    playouts: Option<usize>,
+    time: Option<Duration>,
+    // Time for the next move given by `Player::set_move_time`, which takes
+    // the place of `time`
+    move_time: Option<Duration>,
+    threads: usize,
+    total: usize,
+}
+
+impl Mcts {
+    /// Search for a move for the side to move in `game`
+    pub fn new(game: Game) -> Mcts {
+        Mcts::with_rng(game, Rng::from_time())
+    }
+
+    /// Search which makes the same choices every time for the same seed, as
+    /// long as it only uses one thread
+    pub fn with_seed(game: Game, seed: u64) -> Mcts {
+        Mcts::with_rng(game, Rng::new(seed))
+    }
+
+    fn with_rng(game: Game, mut rng: Rng) -> Mcts {
+        let tree = Tree::new(&game, &mut rng);
+        let size = game.size();
+
+        Mcts {
+            game: game,
+            tree: Arc::new(Mutex::new(tree)),
+            rng: rng,
+            policy: None,
+            network: None,
+            settings: Settings {
+                exploration: 0.5,
+                rave: 500.0,
+                max_moves: 3 * size * size,
+                prior: 1.0,
+                urgency: 1.0,
+            },
+            playouts: Some(1000),
+            time: None,
+            move_time: None,
+            threads: 1,
+            total: 0,
+        }
+    }
+
+    /// Stop after this many playouts (None for no limit)
+    pub fn set_playouts(&mut self, playouts: Option<usize>) { self.playouts = playouts }
+
+    /// Stop after this long (None for no limit), unless a time for the move
+    /// is given through `Player::set_move_time`
+    pub fn set_time(&mut self, time: Option<Duration>) { self.time = time }
+
+    /// Number of threads to search with
+    pub fn set_threads(&mut self, threads: usize) { self.threads = max(threads, 1) }
+
+    /// Guide the playouts with a table of pattern weights, rather than
+    /// playing them uniformly at random
+    pub fn set_policy(&mut self, weights: Option<Weights>) { self.policy = weights.map(Arc::new) }
+
+    /// Evaluate leaves with a neural network rather than playouts. Moves
+    /// which haven't been tried yet are taken to be losses, so the network's
+    /// probabilities decide which to try first.
+    pub fn set_network(&mut self, network: Option<Network>) {
+        if let Some(ref n) = network { assert_eq!(n.size(), self.game.size(), "network is for a different board size") }
+        self.settings.urgency = if network.is_some() { 0.0 } else { 1.0 };
+        self.network = network.map(Arc::new);
+    }
+
+    /// Weight of the network's move probabilities in choosing which moves to
+    /// explore
+    pub fn set_prior(&mut self, c: f32) { self.settings.prior = c }
+
+    /// Weight of the UCT exploration term
+    pub fn set_exploration(&mut self, c: f32) { self.settings.exploration = c }
+
+    /// Number of visits at which a node's real results and its AMAF results
+    /// count equally; 0 turns RAVE off
+    pub fn set_rave(&mut self, equivalence: f32) { self.settings.rave = equivalence }
+
+    /// Playouts stop after this many moves and are scored as they stand
+    pub fn set_max_moves(&mut self, moves: usize) { self.settings.max_moves = moves }
+
+    /// Total number of playouts (or network evaluations) so far
+    pub fn playouts(&self) -> usize { self.total }
+
+    pub fn game(&self) -> &Game { &self.game }
+
+    /// Throw away the tree and start a new search from `game`, keeping the
+    /// settings. A different board size resets the playout length.
+    pub fn reset(&mut self, game: Game) {
+        if let Some(ref n) = self.network { assert_eq!(n.size(), game.size(), "network is for a different board size") }
+        if game.size() != self.game.size() { self.settings.max_moves = 3 * game.size() * game.size() }
+
+        self.tree = Arc::new(Mutex::new(Tree::new(&game, &mut self.rng)));
+        self.game = game;
+        self.total = 0;
+    }
+
+    /// Run playouts until one of the limits is reached, and return the
+    /// results for each move. With no limits set this would never finish, so
+    /// it returns straight away.
+    pub fn search(&mut self) -> Vec<MoveStats> {
+        let time = self.move_time.or(self.time);
+        if self.playouts.is_none() && time.is_none() { return self.stats() }
+
+        // A time too long to have an end is no limit at all
+        let deadline = time.and_then(|t| Instant::now().checked_add(t));
+        let limit = self.playouts.unwrap_or(usize::max_value());
+        let count = Arc::new(AtomicUsize::new(0));
+
+        let mut workers = Vec::new();
+        for _ in 1..self.threads {
+            let tree = self.tree.clone();
+            let game = self.game.clone();
+            let settings = self.settings;
+            let evaluator = self.evaluator();
+            let count = count.clone();
+            workers.push(thread::spawn(move || {
+                work(&tree, &game, settings, evaluator, &count, limit, deadline)
+            }));
+        }
+
+        let evaluator = self.evaluator();
+        let mut done = work(&self.tree, &self.game, self.settings, evaluator, &count, limit, deadline);
+        for w in workers {
+            done += w.join().expect("search thread panicked");
+        }
+        self.total += done;
+
+        self.stats()
+    }
+
+    // The network, or a player for one thread's playouts
+    fn evaluator(&mut self) -> Evaluator {
+        if let Some(ref n) = self.network { return Evaluator::Network(n.clone()) }
+
+        let seed = self.rng.next_u64();
+        Evaluator::Playout(match self.policy {
+            Some(ref w) => Box::new(PatternPlayer::with_seed(w.clone(), seed)),
+            None => Box::new(RandomPlayer::with_seed(seed)),
+        })
+    }
+
+    /// Results for each move at the root, most visited first
+    pub fn stats(&self) -> Vec<MoveStats> {
+        let tree = self.tree.lock().expect("search thread panicked");
+        let mut stats: Vec<_> = tree.nodes[0].children.iter()
+            .map(|&c| &tree.nodes[c])
+            .map(|n| MoveStats {
+                mv: n.mv,
+                visits: n.visits as usize,
+                winrate: if n.visits > 0 { n.wins / n.visits as f32 } else { 0.0 },
+            })
+            .collect();
+        stats.sort_by(|a, b| b.visits.cmp(&a.visits));
+        stats
+    }
+
+    /// The most visited move
+    pub fn best_move(&self) -> Move {
+        self.stats().first().map_or(Move::Pass, |s| s.mv)
+    }
+}
+
+// Run playouts until `count` reaches `limit` or the deadline passes, and
+// return how many this thread did
+fn work(tree: &Mutex<Tree>, game: &Game, settings: Settings, mut evaluator: Evaluator,
+        count: &AtomicUsize, limit: usize, deadline: Option<Instant>) -> usize {
+    let mut done = 0;
+    let lazy = match evaluator { Evaluator::Network(_) => true, Evaluator::Playout(_) => false };
+
+    loop {
+        if deadline.map_or(false, |d| Instant::now() >= d) { break }
+        if count.fetch_add(1, Ordering::SeqCst) >= limit { break }
+
+        let mut game = game.clone();
+        let start = game.history().len();
+        let path = tree.lock().expect("search thread panicked").descend(&mut game, settings, lazy);
+
+        // Black's expected result, from 0 to 1
+        let won = |w: Option<Stone>| match w { Some(Stone::Black) => 1.0, Some(_) => 0.0, None => 0.5 };
+        let (black, eval) = match evaluator {
+            _ if game.over() => (won(game.score().winner()), None),
+            Evaluator::Playout(ref mut p) => (won(p.playout(&mut game, settings.max_moves).winner()), None),
+            Evaluator::Network(ref n) => {
+                let e = n.evaluate(&game);
+                let v = (e.value() + 1.0) / 2.0;
+                (if game.tomove() == Stone::Black { v } else { 1.0 - v }, Some(e))
+            },
+        };
+
+        let mut tree = tree.lock().expect("search thread panicked");
+        let leaf = *path.last().expect("empty path");
+        if let Some(ref e) = eval {
+            if !tree.nodes[leaf].expanded { tree.expand(leaf, &game, Some(e)) }
+        }
+        tree.record(&path, &game, start, black);
+        done += 1;
+    }
+
+    done
+}
+
+impl Player for Mcts {
+    fn genmove(&mut self, game: &Game, colour: Stone) -> Move {
+        let mut game = game.clone();
+        game.set_tomove(colour);
+        self.reset(game);
+        self.search();
+        self.best_move()
+    }
+
+    fn set_move_time(&mut self, time: Option<Duration>) { self.move_time = time }
+}
+
+impl Tree {
+    fn new(game: &Game, rng: &mut Rng) -> Tree {
+        let root = Node::new(Move::Pass, !game.tomove());
+        Tree { nodes: vec![root], rng: Rng::new(rng.next_u64()) }
+    }
+
+    // Add a child for every sensible legal move, in random order, with the
+    // network's probabilities if there are any
+    fn expand(&mut self, node: usize, game: &Game, eval: Option<&Evaluation>) {
+        let colour = game.tomove();
+        let board = game.board();
+        let mut moves: Vec<_> = board.locations()
+            .filter(|l| board.get(l).is_none())
+            .filter(|l| !(board.eyelike(l, colour) && board.true_eye(l, colour)))
+            .map(Move::Play)
+            .filter(|&m| game.legal(colour, m))
+            .collect();
+        moves.push(Move::Pass);
+
+        for i in (1..moves.len()).rev() {
+            let j = self.rng.below(i + 1);
+            moves.swap(i, j);
+        }
+
+        for mv in moves {
+            let mut n = Node::new(mv, colour);
+            n.prior = eval.map_or(0.0, |e| e.prior(mv));
+            self.nodes.push(n);
+            let id = self.nodes.len() - 1;
+            self.nodes[node].children.push(id);
+        }
+        self.nodes[node].expanded = true;
+    }
+
+    // Child with the best mix of results and exploration
+    fn select(&self, node: usize, settings: Settings) -> usize {
+        let parent = &self.nodes[node];
+        let log = (parent.visits.max(1) as f32).ln();
+        let root = (parent.visits as f32).sqrt();
+        let mut best = (f32::NEG_INFINITY, parent.children[0]);
+
+        for &c in &parent.children {
+            let n = &self.nodes[c];
+            let visits = n.visits as f32;
+            let q = if n.visits > 0 { n.wins / visits } else { settings.urgency };
+            // Passes never get AMAF results, so they go on their real ones alone
+            let amaf = if n.amaf_visits > 0 { n.amaf_wins / n.amaf_visits as f32 } else { settings.urgency };
+            let beta = if settings.rave > 0.0 && n.mv != Move::Pass {
+                (settings.rave / (3.0 * visits + settings.rave)).sqrt()
+            } else {
+                0.0
+            };
+
+            let value = (1.0 - beta) * q + beta * amaf
+                + settings.exploration * (log / (visits + 1.0)).sqrt()
+                + settings.prior * n.prior * root / (visits + 1.0);
+            if value > best.0 { best = (value, c) }
+        }
+
+        best.1
+    }
+
+    // Walk down the tree playing the selected moves in `game`, adding a level
+    // when reaching a node which has already had a playout. If `lazy`, stop
+    // at a node which hasn't been expanded instead, to leave expanding it to
+    // the caller. Every node passed through gets a visit straight away.
+    // Returns the path taken.
+    fn descend(&mut self, game: &mut Game, settings: Settings, lazy: bool) -> Vec<usize> {
+        let mut path = vec![0];
+        let mut node = 0;
+        let mut fresh = false;
+        self.nodes[0].visits += 1;
+
+        while !game.over() && !fresh {
+            if !self.nodes[node].expanded {
+                if lazy { break }
+                self.expand(node, game, None);
+            }
+
+            node = self.select(node, settings);
+            let (colour, mv) = (self.nodes[node].colour, self.nodes[node].mv);
+            game.play(colour, mv).expect("illegal move in tree");
+            path.push(node);
+
+            fresh = self.nodes[node].visits == 0;
+            self.nodes[node].visits += 1;
+        }
+
+        path
+    }
+
+    // Add the result of the finished playout in `game`, `black` for Black
+    // and the rest for White, to the nodes on `path`, and the AMAF results to
+    // their children. The tree's moves start at `start` in the game's history.
+    fn record(&mut self, path: &[usize], game: &Game, start: usize, black: f32) {
+        let result = |c: Stone| if c == Stone::Black { black } else { 1.0 - black };
+
+        // Where each player first played each point, after the root
+        let size = game.size();
+        let mut first = vec![usize::max_value(); 2 * size * size];
+        let slot = |c: Stone, l: &Location| {
+            (l.col() * size + l.row()) * 2 + if c == Stone::Black { 0 } else { 1 }
+        };
+        for (i, t) in game.history()[start..].iter().enumerate() {
+            if let Move::Play(l) = t.mv() {
+                let s = slot(t.colour(), &l);
+                if first[s] > i { first[s] = i }
+            }
+        }
+
+        for (depth, &id) in path.iter().enumerate() {
+            self.nodes[id].wins += result(self.nodes[id].colour);
+
+            // Every child whose move its player made later on gets an AMAF result
+            for i in 0..self.nodes[id].children.len() {
+                let c = self.nodes[id].children[i];
+                let n = &mut self.nodes[c];
+                if let Move::Play(l) = n.mv {
+                    let f = first[slot(n.colour, &l)];
+                    if f >= depth && f != usize::max_value() {
+                        n.amaf_visits += 1;
+                        n.amaf_wins += result(n.colour);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use std::time::Duration;
+
+    use super::Mcts;
+    use board::Board;
+    use features::PLANES;
+    use game::{Game, Move};
+    use location::Location;
+    use nn::Network;
+    use policy::Weights;
+    use rules::Ruleset;
+    use player::Player;
+    use stone::Stone::{Black, White};
+
+    fn game(pos: &str) -> Game {
+        let board = Board::from_str(pos).expect("board");
+        let mut game = Game::new(board.size(), Ruleset::chinese());
+        for l in board.locations() {
+            game.setup(l, board.get(l));
+        }
+        game.set_komi(5.5);
+        game
+    }
+
+    #[test] fn capture() {
+        // Black captures the two White stones in atari before they run away
+        let g = game("\
+. . . . .
+. . . . .
+. # # . .
+# O O . .
+. # # . .
+");
+        let mut m = Mcts::with_seed(g, 3);
+        m.set_playouts(Some(600));
+        let stats = m.search();
+
+        assert_eq!(m.playouts(), 600);
+        assert_eq!(stats.iter().map(|s| s.visits()).sum::<usize>(), 600);
+        assert_eq!(m.best_move(), Move::Play(Location::from_str("D2").unwrap()));
+        assert!(stats[0].winrate() > 0.5);
+    }
+
+    #[test] fn player() {
+        // Searching as a Player starts afresh from each position it's given
+        let mut m = Mcts::with_seed(Game::new(9, Ruleset::chinese()), 3);
+        m.set_playouts(Some(600));
+        let g = game("\
+. . . . .
+. . . . .
+. # # . .
+# O O . .
+. # # . .
+");
+        assert_eq!(m.genmove(&g, Black), Move::Play(Location::from_str("D2").unwrap()));
+        assert_eq!(m.game().size(), 5);
+        assert_eq!(m.playouts(), 600);
+    }
+
+    #[test] fn opening() {
+        // There's no point passing on an empty board
+        let mut m = Mcts::with_seed(Game::new(5, Ruleset::chinese()), 1);
+        m.set_playouts(Some(300));
+        let _ = m.search();
+        assert!(m.best_move() != Move::Pass);
+    }
+
+    #[test] fn threads() {
+        let g = game("\
+. . . . .
+. . . . .
+. # # . .
+# O O . .
+. # # . .
+");
+        let mut m = Mcts::with_seed(g, 3);
+        m.set_threads(4);
+        m.set_playouts(Some(600));
+        let stats = m.search();
+
+        // Once the threads finish, the virtual losses are all resolved
+        assert_eq!(m.playouts(), 600);
+        assert_eq!(stats.iter().map(|s| s.visits()).sum::<usize>(), 600);
+        assert!(stats.iter().all(|s| s.winrate() >= 0.0 && s.winrate() <= 1.0));
+
+        // Which move wins out depends on how the threads interleave, but
+        // with two stones in atari Black should be ahead
+        assert!(stats[0].winrate() > 0.5, "{:?}", stats[0]);
+    }
+
+    #[test] fn policy() {
+        let g = game("\
+. . . . .
+. . . . .
+. # # . .
+# O O . .
+. # # . .
+");
+        let mut m = Mcts::with_seed(g, 3);
+        m.set_policy(Some(Weights::mogo()));
+        m.set_playouts(Some(600));
+        let _ = m.search();
+        assert_eq!(m.best_move(), Move::Play(Location::from_str("D2").unwrap()));
+    }
+
+    #[test] fn network() {
+        // Inputs ignored, an even position, and a strong liking for B2
+        let zeros = |n| vec!["0"; n].join(" ");
+        let mut bias = vec!["0"; 10];
+        bias[4] = "5";
+        let text = format!("gonet {} 1 0 3 1\n{}\n0\n0 0\n0 0\n{}\n{}\n0\n0\n{}\n0\n0\n0\n",
+                           PLANES, zeros(PLANES * 9), zeros(180), bias.join(" "), zeros(9));
+        let net: Network = text.parse().expect("network");
+
+        let mut m = Mcts::with_seed(Game::new(3, Ruleset::chinese()), 1);
+        m.set_network(Some(net));
+        m.set_playouts(Some(100));
+        let stats = m.search();
+
+        assert_eq!(m.playouts(), 100);
+        assert_eq!(stats.iter().map(|s| s.visits()).sum::<usize>(), 99);
+        assert_eq!(m.best_move(), Move::Play(Location::from_str("B2").unwrap()));
+        assert!(stats[0].visits() > 4 * stats[1].visits());
+        assert!(stats.iter().all(|s| s.winrate() == 0.5));
+    }
+
+    #[test] fn send() {
+        fn check<T: Send + Sync>() {}
+        check::<Board>();
+        check::<Game>();
+        check::<Mcts>();
+    }
+
+    #[test] fn limits() {
+        let mut g = Game::new(5, Ruleset::chinese());
+        g.set_tomove(White);
+
+        let mut m = Mcts::with_seed(g, 1);
+        m.set_playouts(None);
+        m.set_time(Some(Duration::from_millis(50)));
+        let _ = m.search();
+        assert!(m.playouts() > 0);
+
+        // No limits at all doesn't run forever
+        m.set_time(None);
+        let before = m.playouts();
+        let _ = m.search();
+        assert_eq!(m.playouts(), before);
+        assert!(m.stats().iter().all(|s| s.winrate() >= 0.0 && s.winrate() <= 1.0));
+        assert_eq!(m.game().tomove(), White);
+    }
+}
@@ -0,0 +1,344 @@
+// Policy and value network inference on the CPU.
+//
+// The network is the usual residual tower: a 3x3 convolution from the input
+// planes to the tower's channels, then residual blocks of two 3x3
+// convolutions, and two heads. The policy head is a 1x1 convolution to two
+// channels followed by a fully connected layer giving a probability for every
+// point and pass (last); the value head is a 1x1 convolution to one channel,
+// a fully connected hidden layer and a single output squashed into -1 (loss)
+// to 1 (win) for the player to move. Every layer but the outputs is followed
+// by a ReLU. Batch normalisation should be folded into the convolutions'
+// weights and biases when exporting.
+//
+// The inputs are planes as laid out by `features::encode`, and convolution
+// weights are (out, in, row, col), so a network trained on what the SGF
+// exporter writes out can be loaded as it is.
+//
+// A weights file is text. The first line is
+//
+//     gonet <inputs> <channels> <blocks> <size> <value hidden>
+//
+// and every following line holds one tensor, its values separated by spaces,
+// weights before biases:
+//
+//     input convolution             channels x inputs x 3 x 3, channels
+//     each block's 2 convolutions   channels x channels x 3 x 3, channels
+//     policy convolution            2 x channels, 2
+//     policy fully connected        (size^2 + 1) x 2 size^2, size^2 + 1
+//     value convolution             1 x channels, 1
+//     value hidden                  hidden x size^2, hidden
+//     value output                  1 x hidden, 1
+//
+// Empty lines and lines starting with `#` are ignored.
+
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+use features::{self, PLANES};
+use game::{Game, Move};
+
+// Convolution over the whole board, padded with zeros
+#[derive(Debug, Clone)]
+struct Conv {
+    inputs: usize,
+    outputs: usize,
+    kernel: usize,
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+impl Conv {
+    fn apply(&self, input: &[f32], size: usize) -> Vec<f32> {
+        let area = size * size;
+        let r = (self.kernel / 2) as isize;
+        let mut out: Vec<f32> = self.bias.iter().flat_map(|&b| vec![b; area]).collect();
+
+        for o in 0..self.outputs {
+            for i in 0..self.inputs {
+                for ky in 0..self.kernel {
+                    for kx in 0..self.kernel {
+                        let w = self.weights[((o * self.inputs + i) * self.kernel + ky) * self.kernel + kx];
+                        if w == 0.0 { continue }
+                        let (dy, dx) = (ky as isize - r, kx as isize - r);
+
+                        for y in 0..size {
+                            let sy = y as isize + dy;
+                            if sy < 0 || sy >= size as isize { continue }
+                            for x in 0..size {
+                                let sx = x as isize + dx;
+                                if sx < 0 || sx >= size as isize { continue }
+                                out[o * area + y * size + x] += w * input[i * area + sy as usize * size + sx as usize];
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        out
+    }
+}
+
+// Fully connected layer
+#[derive(Debug, Clone)]
+struct Dense {
+    inputs: usize,
+    weights: Vec<f32>,
+    bias: Vec<f32>,
+}
+
+impl Dense {
+    fn apply(&self, input: &[f32]) -> Vec<f32> {
+        self.bias.iter().enumerate()
+            .map(|(o, &b)| {
+                let row = &self.weights[o * self.inputs..(o + 1) * self.inputs];
+                b + row.iter().zip(input).map(|(w, x)| w * x).sum::<f32>()
+            })
+            .collect()
+    }
+}
+
+fn relu(v: &mut [f32]) {
+    for x in v.iter_mut() { if *x < 0.0 { *x = 0.0 } }
+}
+
+/// Output of the network for one position
+#[derive(Debug, Clone, PartialEq)]
+pub struct Evaluation {
+    size: usize,
+    policy: Vec<f32>,
+    value: f32,
+}
+
+impl Evaluation {
+    /// Probability of each move, indexed by `row * size + col` with pass last
+    pub fn policy(&self) -> &[f32] { &self.policy }
+
+    /// Probability of one move
+    pub fn prior(&self, mv: Move) -> f32 {
+        match mv {
+            Move::Play(l) => self.policy[l.row() * self.size + l.col()],
+            Move::Pass => self.policy[self.size * self.size],
+        }
+    }
+
+    /// Expected result for the player to move, from -1 for a loss to 1 for a win
+    pub fn value(&self) -> f32 { self.value }
+}
+
+#[derive(Debug, Clone)]
+pub struct Network {
+    size: usize,
+    input: Conv,
+    blocks: Vec<(Conv, Conv)>,
+    policy_conv: Conv,
+    policy_fc: Dense,
+    value_conv: Conv,
+    value_hidden: Dense,
+    value_fc: Dense,
+}
+
+impl Network {
+    /// Read a network from a file in the format described in this module
+    pub fn load<P>(path: P) -> io::Result<Network>
+        where P: AsRef<Path>
+    {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+        text.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Size of board the network plays on
+    pub fn size(&self) -> usize { self.size }
+
+    /// Number of input planes
+    pub fn inputs(&self) -> usize { self.input.inputs }
+
+    pub fn channels(&self) -> usize { self.input.outputs }
+    pub fn blocks(&self) -> usize { self.blocks.len() }
+
+    /// Run the network on input planes
+    pub fn forward(&self, planes: &[f32]) -> Evaluation {
+        let size = self.size;
+        assert_eq!(planes.len(), self.inputs() * size * size, "wrong number of inputs");
+
+        let mut x = self.input.apply(planes, size);
+        relu(&mut x);
+
+        for &(ref a, ref b) in &self.blocks {
+            let mut y = a.apply(&x, size);
+            relu(&mut y);
+            let mut y = b.apply(&y, size);
+            for (y, x) in y.iter_mut().zip(&x) { *y += *x }
+            relu(&mut y);
+            x = y;
+        }
+
+        let mut p = self.policy_conv.apply(&x, size);
+        relu(&mut p);
+        let mut policy = self.policy_fc.apply(&p);
+        let max = policy.iter().cloned().fold(::std::f32::NEG_INFINITY, f32::max);
+        for p in policy.iter_mut() { *p = (*p - max).exp() }
+        let total: f32 = policy.iter().sum();
+        for p in policy.iter_mut() { *p /= total }
+
+        let mut v = self.value_conv.apply(&x, size);
+        relu(&mut v);
+        let mut h = self.value_hidden.apply(&v);
+        relu(&mut h);
+        let value = self.value_fc.apply(&h)[0].tanh();
+
+        Evaluation { size: size, policy: policy, value: value }
+    }
+
+    /// Evaluate the position in `game` for the player to move
+    pub fn evaluate(&self, game: &Game) -> Evaluation {
+        assert_eq!(game.size(), self.size, "network is for a different board size");
+        assert_eq!(self.inputs(), PLANES, "network doesn't take the standard feature planes");
+        self.forward(&features::encode(game))
+    }
+}
+
+impl FromStr for Network {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut lines = s.lines()
+            .map(|l| l.trim())
+            .enumerate()
+            .filter(|&(_, l)| !l.is_empty() && !l.starts_with('#'));
+
+        let header: Vec<usize> = match lines.next() {
+            Some((_, l)) if l.starts_with("gonet ") =>
+                try!(l.split_whitespace().skip(1).map(|w| w.parse()).collect::<Result<_, _>>()
+                     .map_err(|_| "bad header".to_string())),
+            _ => return Err("not a network file".to_string()),
+        };
+        if header.len() != 5 { return Err("bad header".to_string()) }
+        let (inputs, channels, blocks, size, hidden) = (header[0], header[1], header[2], header[3], header[4]);
+        let area = size * size;
+
+        // The next tensor, which must have `len` values
+        let mut tensor = |len: usize| -> Result<Vec<f32>, String> {
+            let (n, l) = try!(lines.next().ok_or("missing weights".to_string()));
+            let v: Vec<f32> = try!(l.split_whitespace().map(|w| w.parse()).collect::<Result<_, _>>()
+                                   .map_err(|_| format!("line {}: bad number", n + 1)));
+            if v.len() != len {
+                return Err(format!("line {}: expected {} values, found {}", n + 1, len, v.len()))
+            }
+            Ok(v)
+        };
+
+        let mut conv = |inputs: usize, outputs: usize, kernel: usize| -> Result<Conv, String> {
+            let weights = try!(tensor(outputs * inputs * kernel * kernel));
+            let bias = try!(tensor(outputs));
+            Ok(Conv { inputs: inputs, outputs: outputs, kernel: kernel, weights: weights, bias: bias })
+        };
+
+        let input = try!(conv(inputs, channels, 3));
+        let mut tower = Vec::new();
+        for _ in 0..blocks {
+            let a = try!(conv(channels, channels, 3));
+            let b = try!(conv(channels, channels, 3));
+            tower.push((a, b));
+        }
+        let policy_conv = try!(conv(channels, 2, 1));
+        // Fully connected layers are stored just like 1x1 convolutions
+        let dense = |c: Conv| Dense { inputs: c.inputs, weights: c.weights, bias: c.bias };
+        let policy_fc = dense(try!(conv(2 * area, area + 1, 1)));
+        let value_conv = try!(conv(channels, 1, 1));
+        let value_hidden = dense(try!(conv(area, hidden, 1)));
+        let value_fc = dense(try!(conv(hidden, 1, 1)));
+
+        if let Some((n, _)) = lines.next() { return Err(format!("line {}: too many weights", n + 1)) }
+
+        Ok(Network {
+            size: size,
+            input: input,
+            blocks: tower,
+            policy_conv: policy_conv,
+            policy_fc: policy_fc,
+            value_conv: value_conv,
+            value_hidden: value_hidden,
+            value_fc: value_fc,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::Network;
+    use board::Board;
+    use features::PLANES;
+    use game::{Game, Move};
+    use rules::Ruleset;
+
+    // A network for 3x3 boards with 2 channels, one block and 2 hidden
+    // units, its weights made by `f` from their position in the file
+    fn tiny<F>(f: F) -> String
+        where F: Fn(usize) -> f32
+    {
+        let (c, area, hidden) = (2, 9, 2);
+        let lens = [c * PLANES * 9, c,
+                    c * c * 9, c, c * c * 9, c,
+                    2 * c, 2, (area + 1) * 2 * area, area + 1,
+                    c, 1, hidden * area, hidden, hidden, 1];
+
+        let mut text = format!("gonet {} {} 1 3 {}\n", PLANES, c, hidden);
+        let mut k = 0;
+        for &n in &lens {
+            let values: Vec<_> = (k..k + n).map(|i| f(i).to_string()).collect();
+            text.push_str(&values.join(" "));
+            text.push('\n');
+            k += n;
+        }
+        text
+    }
+
+    #[test] fn zero() {
+        let net: Network = tiny(|_| 0.0).parse().expect("network");
+        assert_eq!((net.size(), net.inputs(), net.channels(), net.blocks()), (3, PLANES, 2, 1));
+
+        let e = net.evaluate(&Game::new(3, Ruleset::chinese()));
+        assert!(e.policy().iter().all(|&p| (p - 0.1).abs() < 1e-6));
+        assert_eq!(e.prior(Move::Pass), e.policy()[9]);
+        assert_eq!(e.value(), 0.0);
+    }
+
+    #[test] fn fixed() {
+        // Outputs checked against an independent implementation
+        let net: Network = tiny(|k| ((k * 37) % 23) as f32 / 40.0 - 11.0 / 40.0).parse().expect("network");
+        let input: Vec<_> = (0..PLANES * 9).map(|i| ((i * 13) % 7) as f32 / 7.0).collect();
+        let e = net.forward(&input);
+
+        let policy = [0.13398, 0.10186, 0.07744, 0.1157, 0.10107, 0.07683, 0.10381, 0.07892, 0.11952, 0.09087];
+        for (p, q) in e.policy().iter().zip(&policy) {
+            assert!((p - q).abs() < 1e-4, "{:?}", e.policy());
+        }
+        assert!((e.value() + 0.17324).abs() < 1e-4, "{}", e.value());
+
+        // and the same every time
+        let b = Board::from_str(". # .\n. O .\n. . .\n").expect("board");
+        let mut game = Game::new(3, Ruleset::chinese());
+        for l in b.locations() { game.setup(l, b.get(l)); }
+        let e = net.evaluate(&game);
+        assert_eq!(e, net.evaluate(&game));
+        assert!((e.policy().iter().sum::<f32>() - 1.0).abs() < 1e-5);
+        assert!(e.value() > -1.0 && e.value() < 1.0);
+    }
+
+    #[test] fn errors() {
+        assert_eq!(Network::from_str("# empty\n").unwrap_err(), "not a network file");
+        assert_eq!(Network::from_str("gonet 1 2\n").unwrap_err(), "bad header");
+        assert_eq!(Network::from_str("gonet 1 1 0 1 1\n1 2\n").unwrap_err(), "line 2: expected 9 values, found 2");
+        assert_eq!(Network::from_str("gonet 1 1 0 1 1\n1 2 3 4 5 6 7 8 x\n").unwrap_err(), "line 2: bad number");
+
+        let mut text = tiny(|_| 0.0);
+        text.push_str("0\n");
+        assert!(Network::from_str(&text).unwrap_err().ends_with("too many weights"));
+    }
+}
@@ -0,0 +1,327 @@
+// Patterns of the points around a location.
+//
+// Each point in the neighbourhood is empty, black, white or off the edge of
+// the board, packed two bits per point into a single integer. The 3x3 pattern
+// (the 8 points around the centre) fits in 16 bits, the diamond (points at
+// most two steps away) in 24 and the 5x5 square in 48. The point at the
+// centre isn't included. Patterns can be normalised so that all 8 rotations
+// and reflections of a shape give the same id.
+
+use std::fmt::{self, Display};
+
+use board::Board;
+use location::Location;
+use stone::Stone;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Neighbourhood {
+    /// The 8 points around the centre
+    Square3,
+    /// The 12 points within two steps of the centre
+    Diamond,
+    /// The 24 points of the 5x5 square around the centre
+    Square5,
+}
+
+/// One point in a pattern
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Cell {
+    Empty,
+    Black,
+    White,
+    Edge,
+}
+
+impl Cell {
+    fn from_bits(b: u64) -> Cell {
+        match b & 3 {
+            0 => Cell::Empty,
+            1 => Cell::Black,
+            2 => Cell::White,
+            _ => Cell::Edge,
+        }
+    }
+
+    fn bits(&self) -> u64 {
+        match *self {
+            Cell::Empty => 0,
+            Cell::Black => 1,
+            Cell::White => 2,
+            Cell::Edge => 3,
+        }
+    }
+}
+
+impl From<Option<Stone>> for Cell {
+    fn from(s: Option<Stone>) -> Cell {
+        match s {
+            None => Cell::Empty,
+            Some(Stone::Black) => Cell::Black,
+            Some(Stone::White) => Cell::White,
+        }
+    }
+}
+
+/// One of the 8 rotations and reflections of the square
+pub fn transform(t: usize, (x, y): (i32, i32)) -> (i32, i32) {
+    let (x, y) = if t & 4 != 0 { (y, x) } else { (x, y) };
+    (if t & 1 != 0 { -x } else { x }, if t & 2 != 0 { -y } else { y })
+}
+
+// Offsets of the points in a neighbourhood, and for each symmetry where each
+// point moves to
+struct Layout {
+    offsets: Vec<(i32, i32)>,
+    perms: Vec<Vec<usize>>,
+}
+
+impl Layout {
+    fn new<F>(radius: i32, inside: F) -> Layout
+        where F: Fn(i32, i32) -> bool
+    {
+        let mut offsets = Vec::new();
+        for dy in -radius..radius + 1 {
+            for dx in -radius..radius + 1 {
+                if (dx, dy) != (0, 0) && inside(dx, dy) { offsets.push((dx, dy)) }
+            }
+        }
+
+        let perms = (0..8).map(|t| {
+            offsets.iter()
+                .map(|&o| offsets.iter().position(|&p| p == transform(t, o)).expect("not symmetric"))
+                .collect()
+        }).collect();
+
+        Layout { offsets: offsets, perms: perms }
+    }
+}
+
+lazy_static!{
+    static ref SQUARE3: Layout = Layout::new(1, |_, _| true);
+    static ref DIAMOND: Layout = Layout::new(2, |x, y| x.abs() + y.abs() <= 2);
+    static ref SQUARE5: Layout = Layout::new(2, |_, _| true);
+}
+
+impl Neighbourhood {
+    fn layout(&self) -> &'static Layout {
+        match *self {
+            Neighbourhood::Square3 => &SQUARE3,
+            Neighbourhood::Diamond => &DIAMOND,
+            Neighbourhood::Square5 => &SQUARE5,
+        }
+    }
+
+    /// Offsets (column, row) from the centre of each point, in the order they
+    /// are packed into a pattern
+    pub fn offsets(&self) -> &'static [(i32, i32)] { &self.layout().offsets }
+
+    /// Number of points in the neighbourhood
+    pub fn len(&self) -> usize { self.offsets().len() }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Pattern {
+    shape: Neighbourhood,
+    id: u64,
+}
+
+impl Pattern {
+    /// The pattern of the points in `shape` around `loc`
+    pub fn new<L>(board: &Board, loc: L, shape: Neighbourhood) -> Pattern
+        where L: AsRef<Location>
+    {
+        let loc = loc.as_ref();
+        let size = board.size() as i32;
+        let mut id = 0;
+
+        for (i, &(dx, dy)) in shape.offsets().iter().enumerate() {
+            let (c, r) = (loc.col() as i32 + dx, loc.row() as i32 + dy);
+            let cell = if c < 0 || r < 0 || c >= size || r >= size {
+                Cell::Edge
+            } else {
+                Cell::from(board.get(Location::new(c as usize, r as usize)))
+            };
+            id |= cell.bits() << (2 * i);
+        }
+
+        Pattern { shape: shape, id: id }
+    }
+
+    /// The 3x3 pattern around `loc`
+    pub fn square3<L>(board: &Board, loc: L) -> Pattern
+        where L: AsRef<Location>
+    {
+        Pattern::new(board, loc, Neighbourhood::Square3)
+    }
+
+    /// Pattern made of `cells`, in the order of `Neighbourhood::offsets`
+    pub fn from_cells<CI>(shape: Neighbourhood, cells: CI) -> Pattern
+        where CI: IntoIterator<Item=Cell>
+    {
+        let id = cells.into_iter().take(shape.len()).enumerate()
+            .fold(0, |id, (i, c)| id | c.bits() << (2 * i));
+        Pattern { shape: shape, id: id }
+    }
+
+    pub fn shape(&self) -> Neighbourhood { self.shape }
+
+    /// Packed points of the pattern, two bits each
+    pub fn id(&self) -> u64 { self.id }
+
+    /// Point `i`, in the order of `Neighbourhood::offsets`
+    pub fn get(&self, i: usize) -> Cell { Cell::from_bits(self.id >> (2 * i)) }
+
+    /// Point at an offset from the centre
+    pub fn at(&self, dx: i32, dy: i32) -> Option<Cell> {
+        self.shape.offsets().iter().position(|&o| o == (dx, dy)).map(|i| self.get(i))
+    }
+
+    /// The same pattern with Black and White swapped
+    pub fn swap_colours(&self) -> Pattern {
+        let mut id = 0;
+        for i in 0..self.shape.len() {
+            let c = match self.get(i) {
+                Cell::Black => Cell::White,
+                Cell::White => Cell::Black,
+                c => c,
+            };
+            id |= c.bits() << (2 * i);
+        }
+        Pattern { shape: self.shape, id: id }
+    }
+
+    /// The pattern as seen by `colour`, so that its own stones are Black
+    pub fn for_colour(&self, colour: Stone) -> Pattern {
+        match colour {
+            Stone::Black => *self,
+            Stone::White => self.swap_colours(),
+        }
+    }
+
+    /// The pattern rotated or reflected by symmetry `t` (0 to 7)
+    pub fn transform(&self, t: usize) -> Pattern {
+        let perm = &self.shape.layout().perms[t];
+        let mut id = 0;
+        for (i, &p) in perm.iter().enumerate() {
+            id |= self.get(p).bits() << (2 * i);
+        }
+        Pattern { shape: self.shape, id: id }
+    }
+
+    /// The same representative of the pattern for every rotation and
+    /// reflection of it
+    pub fn normalised(&self) -> Pattern {
+        (0..8).map(|t| self.transform(t)).min_by_key(|p| p.id).expect("no symmetries")
+    }
+}
+
+impl Display for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let offsets = self.shape.offsets();
+        let radius = offsets.iter().map(|&(x, _)| x).max().unwrap_or(0);
+
+        for dy in (-radius..radius + 1).rev() {
+            for dx in -radius..radius + 1 {
+                let c = match self.at(dx, dy) {
+                    _ if (dx, dy) == (0, 0) => '*',
+                    Some(Cell::Empty) => '.',
+                    Some(Cell::Black) => '#',
+                    Some(Cell::White) => 'O',
+                    Some(Cell::Edge) => '-',
+                    None => ' ',
+                };
+                try!(write!(f, "{}", c));
+                if dx < radius { try!(write!(f, " ")) }
+            }
+            try!(writeln!(f, ""));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Pattern, Neighbourhood, Cell};
+    use board::Board;
+    use location::Location;
+    use stone::Stone::White;
+
+    fn loc(s: &str) -> Location { Location::from_str(s).unwrap() }
+
+    #[test] fn neighbourhoods() {
+        assert_eq!(Neighbourhood::Square3.len(), 8);
+        assert_eq!(Neighbourhood::Diamond.len(), 12);
+        assert_eq!(Neighbourhood::Square5.len(), 24);
+    }
+
+    #[test] fn extract() {
+        let b = Board::from_str("\
+. . . . .
+. . . . .
+. # . . .
+. O . . .
+. . . . .
+").expect("board");
+
+        let p = Pattern::square3(&b, loc("A2"));
+        assert_eq!(p.at(1, 0), Some(Cell::White));
+        assert_eq!(p.at(1, 1), Some(Cell::Black));
+        assert_eq!(p.at(-1, 0), Some(Cell::Edge));
+        assert_eq!(p.at(0, -1), Some(Cell::Empty));
+        assert_eq!(p.at(2, 0), None);
+        assert_eq!(format!("{}", p), "- . #\n- * O\n- . .\n");
+
+        // Five edge points in the corner
+        let p = Pattern::square3(&b, loc("A1"));
+        assert_eq!((0..8).filter(|&i| p.get(i) == Cell::Edge).count(), 5);
+
+        let p = Pattern::new(&b, loc("C3"), Neighbourhood::Diamond);
+        assert_eq!(p.at(-1, 0), Some(Cell::Black));
+        assert_eq!(p.at(-1, -1), Some(Cell::White));
+        assert_eq!(p.at(-2, 0), Some(Cell::Empty));
+    }
+
+    #[test] fn symmetry() {
+        let a = Board::from_str("\
+. . . . .
+. . . . .
+. . . . .
+. # . . .
+. O . . .
+").expect("board");
+        let b = Board::from_str("\
+. . . O .
+. . . # .
+. . . . .
+. . . . .
+. . . . .
+").expect("board");
+
+        let pa = Pattern::square3(&a, loc("B3"));
+        let pb = Pattern::square3(&b, loc("D3"));
+        assert!(pa != pb);
+        assert_eq!(pa.normalised(), pb.normalised());
+
+        for t in 0..8 {
+            assert_eq!(pa.transform(t).normalised(), pa.normalised());
+        }
+
+        // Colours matter, unless seen from the other side
+        let c = Board::from_str("\
+. . . . .
+. . . . .
+. . . . .
+. O . . .
+. # . . .
+").expect("board");
+        let pc = Pattern::square3(&c, loc("B3"));
+        assert!(pc.normalised() != pa.normalised());
+        assert_eq!(pc.for_colour(White).normalised(), pa.normalised());
+
+        // Near the edge isn't the same as in the open
+        let p5 = Pattern::new(&a, loc("B3"), Neighbourhood::Square5);
+        assert!(p5.normalised() != Pattern::new(&a, loc("C3"), Neighbourhood::Square5).normalised());
+    }
+}
@@ -0,0 +1,136 @@
+// Move generators.
+
+use std::time::Duration;
+
+use board::Board;
+use game::{Game, Move, Score};
+use location::Location;
+use rng::Rng;
+use stone::Stone;
+
+/// Something which can choose moves
+pub trait Player {
+    /// Choose a legal move for `colour` in `game`
+    fn genmove(&mut self, game: &Game, colour: Stone) -> Move;
+
+    /// Spend about this long on the next move, for players that can use
+    /// more or less time; None for no limit beyond their own
+    fn set_move_time(&mut self, _time: Option<Duration>) {}
+
+    /// Play out the game until both sides pass, or `max_moves` moves have
+    /// been played, and score it
+    fn playout(&mut self, game: &mut Game, max_moves: usize) -> Score {
+        for _ in 0..max_moves {
+            if game.over() { break }
+            let colour = game.tomove();
+            let mv = self.genmove(game, colour);
+            game.play(colour, mv).expect("player chose an illegal move");
+        }
+        game.score()
+    }
+}
+
+/// Plays uniformly at random among the legal moves, except that it never
+/// fills in one of its own eyes. Passes when there's nothing else to do.
+#[derive(Debug, Clone)]
+pub struct RandomPlayer {
+    rng: Rng,
+}
+
+impl RandomPlayer {
+    /// Player seeded from the clock
+    pub fn new() -> RandomPlayer { RandomPlayer { rng: Rng::from_time() } }
+
+    /// Player which always makes the same choices for the same seed
+    pub fn with_seed(seed: u64) -> RandomPlayer { RandomPlayer { rng: Rng::new(seed) } }
+
+    // Empty points which aren't one of `colour`'s eyes
+    fn candidates(board: &Board, colour: Stone) -> Vec<Location> {
+        board.locations()
+            .filter(|l| board.get(l).is_none())
+            .filter(|l| !(board.eyelike(l, colour) && board.true_eye(l, colour)))
+            .collect()
+    }
+
+    // Pick candidates at random until one is accepted
+    fn pick<F>(&mut self, mut candidates: Vec<Location>, ok: F) -> Move
+        where F: Fn(Location) -> bool
+    {
+        while !candidates.is_empty() {
+            let i = self.rng.below(candidates.len());
+            let loc = candidates.swap_remove(i);
+            if ok(loc) { return Move::Play(loc) }
+        }
+        Move::Pass
+    }
+
+    /// Choose a move on a bare board, where the only illegal moves are
+    /// suicides; there is no history to check for ko
+    pub fn choose(&mut self, board: &Board, colour: Stone) -> Move {
+        let candidates = RandomPlayer::candidates(board, colour);
+        self.pick(candidates, |l| {
+            let mut b = board.clone();
+            b.play(l, colour) && b.get(l) == Some(colour)
+        })
+    }
+}
+
+impl Player for RandomPlayer {
+    fn genmove(&mut self, game: &Game, colour: Stone) -> Move {
+        let candidates = RandomPlayer::candidates(game.board(), colour);
+        self.pick(candidates, |l| game.legal(colour, Move::Play(l)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Player, RandomPlayer};
+    use board::Board;
+    use game::{Game, Move};
+    use rules::Ruleset;
+    use stone::Stone::{Black, White};
+
+    #[test] fn keeps_eyes() {
+        let b = Board::from_str("\
+. # O .
+# # O O
+O O O .
+. O . O
+").expect("board");
+
+        // Black can't play anywhere but its own eye; White has only eyes too
+        let mut p = RandomPlayer::with_seed(1);
+        assert_eq!(p.choose(&b, Black), Move::Pass);
+        assert_eq!(p.choose(&b, White), Move::Play("A4".parse().unwrap()));
+    }
+
+    #[test] fn seeded() {
+        let game = Game::new(9, Ruleset::default());
+        let a: Vec<_> = (0..10).map(|_| RandomPlayer::with_seed(7).genmove(&game, Black)).collect();
+        assert!(a.iter().all(|&m| m == a[0]));
+    }
+
+    #[test] fn random_games() {
+        let mut p = RandomPlayer::with_seed(42);
+
+        for &rules in &[Ruleset::japanese(), Ruleset::chinese(), Ruleset::new_zealand()] {
+            let mut game = Game::new(7, rules);
+            let _ = p.playout(&mut game, 1000);
+            assert!(game.over());
+
+            let board = game.board();
+            for colour in &[Black, White] {
+                for g in board.groups::<Vec<_>>(*colour) {
+                    assert!(board.liberties::<Vec<_>>(&g).len() > 0);
+                }
+
+                // Every stone played is either on the board or was captured
+                let played = game.history().iter().filter(|t| t.colour() == *colour && t.mv() != Move::Pass).count();
+                let on_board = board.locations().filter(|l| board.get(l) == Some(*colour)).count();
+                assert_eq!(played, on_board + game.captures(!*colour));
+            }
+        }
+    }
+}
@@ -0,0 +1,451 @@
+// Playout policy guided by patterns, in the style of MoGo.
+//
+// Most of the time a strong player answers the last move locally, so the
+// candidates are all near it: capturing a group in atari, saving one of our
+// own groups which the last move put in atari, and the points around the last
+// move whose 3x3 pattern is in the table. One of them is chosen at random in
+// proportion to its weight, and if there are none the move is uniformly random
+// as for `RandomPlayer`.
+//
+// A weights file has one entry per line. Patterns are three rows of three
+// points, top row first, followed by the weight:
+//
+//     XOX .*. ??? 2.0
+//
+// where `X` is a stone of the player to move, `O` an opponent's stone, `.` an
+// empty point, `-` off the edge of the board, `x` anything but the player's
+// stone, `o` anything but the opponent's and `?` anything at all. The centre is
+// the point being played, written `*` or `.`. Each pattern also matches all its
+// rotations and reflections. The weights of the other heuristics are given as
+// `capture 8` and `escape 8`, and lines starting with `#` are comments.
+
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use board::Board;
+use game::{Game, Move};
+use location::Location;
+use pattern::{Cell, Neighbourhood, Pattern};
+use player::{Player, RandomPlayer};
+use rng::Rng;
+use stone::Stone;
+
+// MoGo's hane, cut and edge patterns, as used by most light playout policies.
+// These match for either side, so the colour swapped versions are added too.
+const MOGO: &'static str = "
+capture 8
+escape 8
+
+# Hane
+XOX .*. ??? 2
+XO. .*. ?.? 2
+XO? X*. x.? 2
+XOO .*. ?.? 2
+
+# Diagonal attachment
+.O. X*. ... 2
+
+# Cuts
+XO? O*o ?o? 2
+XO? O*X ??? 2
+?X? O*O ooo 2
+OX? o*O ??? 2
+
+# Edge
+X.? O*? --- 2
+OX? X*O --- 2
+?X? x*O --- 2
+?XO x*x --- 2
+?OX X*O --- 2
+";
+
+/// Weights of the moves suggested by each part of the playout policy
+#[derive(Debug, Clone)]
+pub struct Weights {
+    // By id of the 3x3 pattern as seen by the player to move, with its
+    // stones Black; every symmetry of a pattern has its own entry
+    patterns: HashMap<u64, f32>,
+    capture: f32,
+    escape: f32,
+}
+
+impl Weights {
+    /// Weights with no patterns, which leave the playouts uniformly random
+    pub fn new() -> Weights {
+        Weights { patterns: HashMap::new(), capture: 0.0, escape: 0.0 }
+    }
+
+    /// MoGo's patterns, for either colour
+    pub fn mogo() -> Weights {
+        let mut w: Weights = MOGO.parse().expect("bad built in patterns");
+        let swapped: Vec<_> = w.patterns.iter()
+            .map(|(&id, &v)| (Pattern::from_cells(Neighbourhood::Square3, cells(id)).swap_colours().id(), v))
+            .collect();
+        for (id, v) in swapped {
+            w.patterns.entry(id).or_insert(v);
+        }
+        w
+    }
+
+    /// Read weights from a file in the format described in this module
+    pub fn load<P>(path: P) -> io::Result<Weights>
+        where P: AsRef<Path>
+    {
+        let mut text = String::new();
+        try!(try!(File::open(path)).read_to_string(&mut text));
+        text.parse().map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+
+    /// Weight of capturing a group in atari next to the last move
+    pub fn capture(&self) -> f32 { self.capture }
+    pub fn set_capture(&mut self, w: f32) { self.capture = w }
+
+    /// Weight of saving a group which the last move put in atari
+    pub fn escape(&self) -> f32 { self.escape }
+    pub fn set_escape(&mut self, w: f32) { self.escape = w }
+
+    /// Weight of playing in the middle of a 3x3 pattern, where the player to
+    /// move has the Black stones
+    pub fn pattern(&self, pattern: &Pattern) -> f32 {
+        self.patterns.get(&pattern.id()).map_or(0.0, |&w| w)
+    }
+
+    /// Set the weight of a 3x3 pattern and all its rotations and reflections
+    pub fn set_pattern(&mut self, pattern: &Pattern, w: f32) {
+        assert_eq!(pattern.shape(), Neighbourhood::Square3);
+        for t in 0..8 {
+            let _ = self.patterns.insert(pattern.transform(t).id(), w);
+        }
+    }
+
+    /// Number of patterns, counting each rotation and reflection separately
+    pub fn len(&self) -> usize { self.patterns.len() }
+
+    // Weight of `colour` playing at `loc`
+    fn at(&self, board: &Board, loc: Location, colour: Stone) -> f32 {
+        if self.patterns.is_empty() { return 0.0 }
+        self.pattern(&Pattern::square3(board, loc).for_colour(colour))
+    }
+}
+
+impl Default for Weights {
+    fn default() -> Weights { Weights::new() }
+}
+
+// Points of a packed 3x3 pattern
+fn cells(id: u64) -> Vec<Cell> {
+    (0..8).map(|i| match (id >> (2 * i)) & 3 {
+        0 => Cell::Empty,
+        1 => Cell::Black,
+        2 => Cell::White,
+        _ => Cell::Edge,
+    }).collect()
+}
+
+// Cells which a character in a pattern picture matches
+fn matching(c: char) -> Option<&'static [Cell]> {
+    use pattern::Cell::*;
+
+    Some(match c {
+        'X' => &[Black],
+        'O' => &[White],
+        '.' => &[Empty],
+        '-' => &[Edge],
+        'x' => &[Empty, White, Edge],
+        'o' => &[Empty, Black, Edge],
+        '?' => &[Empty, Black, White, Edge],
+        _ => return None,
+    })
+}
+
+// Every pattern matching the three rows of a picture, top row first
+fn expand(rows: &[&str]) -> Result<Vec<Pattern>, &'static str> {
+    let grid: Vec<Vec<char>> = rows.iter().map(|r| r.chars().collect()).collect();
+    if grid.len() != 3 || grid.iter().any(|r| r.len() != 3) { return Err("pattern must be 3x3") }
+    if grid[1][1] != '*' && grid[1][1] != '.' { return Err("centre of pattern must be empty") }
+
+    let mut choices = Vec::new();
+    for &(dx, dy) in Neighbourhood::Square3.offsets() {
+        let c = grid[(1 - dy) as usize][(dx + 1) as usize];
+        choices.push(try!(matching(c).ok_or("unknown character in pattern")));
+    }
+
+    let mut patterns = vec![Vec::new()];
+    for cs in choices {
+        patterns = patterns.into_iter()
+            .flat_map(|p: Vec<Cell>| cs.iter().map(move |&c| { let mut p = p.clone(); p.push(c); p }))
+            .collect();
+    }
+    Ok(patterns.into_iter().map(|p| Pattern::from_cells(Neighbourhood::Square3, p)).collect())
+}
+
+impl FromStr for Weights {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut weights = Weights::new();
+
+        for (n, line) in s.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') { continue }
+
+            let words: Vec<_> = line.split_whitespace().collect();
+            let err = |e: &str| format!("line {}: {}", n + 1, e);
+            let w: f32 = try!(words.last().expect("empty line").parse().map_err(|_| err("bad weight")));
+
+            match (words.len(), words[0]) {
+                (2, "capture") => weights.capture = w,
+                (2, "escape") => weights.escape = w,
+                (4, _) => {
+                    for p in try!(expand(&words[..3]).map_err(err)) {
+                        weights.set_pattern(&p, w);
+                    }
+                },
+                _ => return Err(err("expected a pattern or a heuristic and a weight")),
+            }
+        }
+
+        Ok(weights)
+    }
+}
+
+/// Plays the moves suggested by a table of weights, falling back to random
+/// moves. Never fills in one of its own eyes.
+#[derive(Debug, Clone)]
+pub struct PatternPlayer {
+    weights: Arc<Weights>,
+    rng: Rng,
+    random: RandomPlayer,
+}
+
+impl PatternPlayer {
+    /// Player seeded from the clock
+    pub fn new<W>(weights: W) -> PatternPlayer
+        where W: Into<Arc<Weights>>
+    {
+        PatternPlayer { weights: weights.into(), rng: Rng::from_time(), random: RandomPlayer::new() }
+    }
+
+    /// Player which always makes the same choices for the same seed
+    pub fn with_seed<W>(weights: W, seed: u64) -> PatternPlayer
+        where W: Into<Arc<Weights>>
+    {
+        PatternPlayer {
+            weights: weights.into(),
+            rng: Rng::new(seed),
+            random: RandomPlayer::with_seed(!seed),
+        }
+    }
+
+    pub fn weights(&self) -> &Weights { &self.weights }
+
+    /// Moves suggested in reply to the last move, with their weights. A point
+    /// suggested for more than one reason gets the sum of their weights.
+    pub fn candidates(&self, game: &Game, colour: Stone) -> Vec<(Location, f32)> {
+        let board = game.board();
+        let weights = &self.weights;
+        let mut cands: Vec<(Location, f32)> = Vec::new();
+
+        let last = match game.history().last().map(|t| t.mv()) {
+            Some(Move::Play(l)) => l,
+            _ => return cands,
+        };
+
+        {
+            let mut add = |l: Location, w: f32| {
+                if w <= 0.0 { return }
+                match cands.iter().position(|&(c, _)| c == l) {
+                    Some(i) => cands[i].1 += w,
+                    None => cands.push((l, w)),
+                }
+            };
+
+            let near: Vec<_> = Some(last).into_iter()
+                .chain(last.neighbours())
+                .filter(|l| board.validloc(l))
+                .collect();
+
+            for &l in &near {
+                let g = match board.group_at(l) { Some(g) => g, None => continue };
+                let libs: Vec<_> = board.liberties(&g);
+                if libs.len() != 1 { continue }
+
+                if g.colour() != colour {
+                    add(libs[0], weights.capture);
+                } else if l != last {
+                    // Run away, if that gets the group more liberties
+                    let mut b = board.clone();
+                    if b.play(libs[0], colour) {
+                        let more = b.group_at(libs[0]).map_or(false, |g| b.liberties::<Vec<_>>(&g).len() > 1);
+                        if more { add(libs[0], weights.escape) }
+                    }
+
+                    // or capture something next to it
+                    for n in g.neighbours().iter() {
+                        let o = match board.group_at(n) { Some(o) => o, None => continue };
+                        if o.colour() == colour { continue }
+                        let olibs: Vec<_> = board.liberties(&o);
+                        if olibs.len() == 1 { add(olibs[0], weights.escape) }
+                    }
+                }
+            }
+
+            for l in last.neighbours().chain(last.diagonals()) {
+                if board.validloc(l) && board.get(l).is_none() {
+                    add(l, weights.at(board, l, colour));
+                }
+            }
+        }
+
+        cands
+    }
+}
+
+impl Player for PatternPlayer {
+    fn genmove(&mut self, game: &Game, colour: Stone) -> Move {
+        let board = game.board();
+        let mut cands = self.candidates(game, colour);
+
+        while !cands.is_empty() {
+            let total: f32 = cands.iter().map(|&(_, w)| w).sum();
+            let mut r = self.rng.float() * total;
+            let mut i = 0;
+            while i + 1 < cands.len() && r >= cands[i].1 {
+                r -= cands[i].1;
+                i += 1;
+            }
+
+            let (l, _) = cands.swap_remove(i);
+            let eye = board.eyelike(l, colour) && board.true_eye(l, colour);
+            if !eye && game.legal(colour, Move::Play(l)) { return Move::Play(l) }
+        }
+
+        self.random.genmove(game, colour)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+    use std::str::FromStr;
+
+    use super::{PatternPlayer, Weights};
+    use board::Board;
+    use game::{Game, Move};
+    use location::Location;
+    use pattern::Pattern;
+    use player::Player;
+    use rules::Ruleset;
+    use stone::Stone::{Black, White};
+
+    fn game(pos: &str, last: &str, colour: ::stone::Stone) -> Game {
+        let board = Board::from_str(pos).expect("board");
+        let last = Location::from_str(last).unwrap();
+        let mut game = Game::new(board.size(), Ruleset::chinese());
+        for l in board.locations().filter(|&l| l != last) {
+            game.setup(l, board.get(l));
+        }
+        game.play(!colour, Move::Play(last)).expect("last move");
+        game
+    }
+
+    #[test] fn parse() {
+        let w: Weights = "
+# comment
+capture 3
+XOX .*. ??? 1.5
+".parse().expect("weights");
+        assert_eq!(w.capture(), 3.0);
+        assert_eq!(w.escape(), 0.0);
+        // 81 fillings of the bottom row, in up to 8 orientations
+        assert!(w.len() > 81);
+
+        let b = Board::from_str("\
+. . . .
+# O # .
+. . . .
+. . . .
+").expect("board");
+        let at = |l: &str| w.pattern(&Pattern::square3(&b, Location::from_str(l).unwrap()));
+        assert_eq!(at("B2"), 1.5);
+        assert_eq!(at("B4"), 1.5);
+        assert_eq!(at("C2"), 0.0);
+        // White sees the colours the other way round
+        assert_eq!(w.pattern(&Pattern::square3(&b, Location::from_str("B2").unwrap()).for_colour(White)), 0.0);
+
+        assert!("XOX .*. ?? 1".parse::<Weights>().is_err());
+        assert!("XOX .O. ??? 1".parse::<Weights>().is_err());
+        assert_eq!("\ncapture x".parse::<Weights>().unwrap_err(), "line 2: bad weight");
+
+        let d = Weights::mogo();
+        assert_eq!(d.pattern(&Pattern::square3(&b, Location::from_str("B2").unwrap()).for_colour(White)), 2.0);
+        assert_eq!(Weights::default().len(), 0);
+    }
+
+    // Deleted when the test ends, whether it passed or not
+    struct TempFile(PathBuf);
+
+    impl Drop for TempFile {
+        fn drop(&mut self) { let _ = fs::remove_file(&self.0); }
+    }
+
+    #[test] fn load() {
+        let file = TempFile(env::temp_dir().join(format!("go-policy-weights-{}.txt", ::std::process::id())));
+        let path = &file.0;
+        File::create(path).and_then(|mut f| f.write_all(b"escape 4\n")).expect("write weights");
+        assert_eq!(Weights::load(path).expect("load").escape(), 4.0);
+
+        File::create(path).and_then(|mut f| f.write_all(b"escape\n")).expect("write weights");
+        assert!(Weights::load(path).is_err());
+    }
+
+    #[test] fn capture() {
+        // White has just played C3, leaving its group in atari at A3
+        let g = game("\
+. . . . .
+. # # . .
+. O O # .
+# # O # .
+. . # . .
+", "C3", Black);
+        let p = PatternPlayer::with_seed(Weights::mogo(), 1);
+        let cands = p.candidates(&g, Black);
+        assert!(cands.iter().any(|&(l, w)| l == Location::from_str("A3").unwrap() && w >= 8.0));
+    }
+
+    #[test] fn escape() {
+        // Black just put the White stone at B2 in atari; White runs out to C2
+        let g = game("\
+. . . . .
+. . . . .
+. # . . .
+# O . . .
+. # . . .
+", "B3", White);
+        let mut p = PatternPlayer::with_seed(Weights::mogo(), 1);
+        let cands = p.candidates(&g, White);
+        assert!(cands.iter().any(|&(l, w)| l == Location::from_str("C2").unwrap() && w >= 8.0));
+
+        let mut w = Weights::new();
+        w.set_escape(1.0);
+        let mut p2 = PatternPlayer::with_seed(w, 1);
+        assert_eq!(p2.genmove(&g, White), Move::Play(Location::from_str("C2").unwrap()));
+        assert!(g.legal(White, p.genmove(&g, White)));
+    }
+
+    #[test] fn playouts() {
+        let mut p = PatternPlayer::with_seed(Weights::mogo(), 9);
+        for _ in 0..5 {
+            let mut game = Game::new(7, Ruleset::japanese());
+            let _ = p.playout(&mut game, 1000);
+            assert!(game.over());
+        }
+    }
+}
@@ -0,0 +1,37 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Small, fast pseudo-random number generator (splitmix64)
+///
+/// Playouts need lots of cheap random numbers, and nothing needs them to be
+/// cryptographically strong.
+#[derive(Debug, Clone)]
+pub struct Rng(u64);
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng { Rng(seed) }
+
+    /// Seed from the clock
+    pub fn from_time() -> Rng {
+        let t = SystemTime::now().duration_since(UNIX_EPOCH).expect("clock before epoch");
+        Rng::new(t.as_secs() ^ ((t.subsec_nanos() as u64) << 32))
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    /// Uniformly distributed in 0..n
+    pub fn below(&mut self, n: usize) -> usize {
+        assert!(n > 0);
+        (self.next_u64() % n as u64) as usize
+    }
+
+    /// Uniformly distributed in [0, 1)
+    pub fn float(&mut self) -> f32 {
+        (self.next_u64() >> 40) as f32 / (1u64 << 24) as f32
+    }
+}
@@ -0,0 +1,191 @@
+use std::str::FromStr;
+use std::fmt::{self, Display};
+
+/// How repeated positions are prohibited
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum KoRule {
+    /// Only immediate recapture of a ko is forbidden
+    Simple,
+    /// A move may not recreate any earlier board position
+    PositionalSuperko,
+    /// A move may not recreate an earlier position with the same player to move
+    SituationalSuperko,
+}
+
+/// How the final result is counted
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Scoring {
+    /// Surrounded empty points plus prisoners
+    Territory,
+    /// Surrounded empty points plus stones on the board
+    Area,
+}
+
+/// Points White receives at the end of a handicap game
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Compensation {
+    None,
+    /// One point for every handicap stone
+    PerStone,
+    /// One point for every handicap stone after the first
+    PerStoneAfterFirst,
+}
+
+impl Compensation {
+    pub fn points(&self, handicap: usize) -> f32 {
+        match *self {
+            Compensation::None => 0.0,
+            Compensation::PerStone => handicap as f32,
+            Compensation::PerStoneAfterFirst =>
+                if handicap > 1 { (handicap - 1) as f32 } else { 0.0 },
+        }
+    }
+}
+
+/// The well-known rule sets
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Rules {
+    Japanese,
+    Chinese,
+    AGA,
+    NewZealand,
+    Ing,
+    TrompTaylor,
+}
+
+#[derive(Debug, Copy, Clone, PartialEq)]
+pub struct Ruleset {
+    rules: Rules,
+    ko: KoRule,
+    suicide: bool,
+    scoring: Scoring,
+    komi: f32,
+    compensation: Compensation,
+}
+
+impl Ruleset {
+    pub fn new(rules: Rules) -> Ruleset {
+        use self::Rules::*;
+        use self::KoRule::*;
+        use self::Scoring::*;
+
+        let (ko, suicide, scoring, komi, compensation) =
+            match rules {
+                Japanese =>     (Simple, false, Territory, 6.5, Compensation::None),
+                Chinese =>      (PositionalSuperko, false, Area, 7.5, Compensation::PerStone),
+                AGA =>          (SituationalSuperko, false, Area, 7.5, Compensation::PerStoneAfterFirst),
+                NewZealand =>   (SituationalSuperko, true, Area, 7.0, Compensation::None),
+                Ing =>          (SituationalSuperko, true, Area, 8.0, Compensation::PerStone),
+                TrompTaylor =>  (PositionalSuperko, true, Area, 7.5, Compensation::None),
+            };
+
+        Ruleset {
+            rules: rules,
+            ko: ko,
+            suicide: suicide,
+            scoring: scoring,
+            komi: komi,
+            compensation: compensation,
+        }
+    }
+
+    pub fn japanese() -> Ruleset { Ruleset::new(Rules::Japanese) }
+    pub fn chinese() -> Ruleset { Ruleset::new(Rules::Chinese) }
+    pub fn aga() -> Ruleset { Ruleset::new(Rules::AGA) }
+    pub fn new_zealand() -> Ruleset { Ruleset::new(Rules::NewZealand) }
+    pub fn ing() -> Ruleset { Ruleset::new(Rules::Ing) }
+    pub fn tromp_taylor() -> Ruleset { Ruleset::new(Rules::TrompTaylor) }
+
+    pub fn rules(&self) -> Rules { self.rules }
+    pub fn ko(&self) -> KoRule { self.ko }
+    pub fn suicide(&self) -> bool { self.suicide }
+    pub fn scoring(&self) -> Scoring { self.scoring }
+    pub fn komi(&self) -> f32 { self.komi }
+    pub fn compensation(&self) -> Compensation { self.compensation }
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset { Ruleset::japanese() }
+}
+
+impl FromStr for Ruleset {
+    type Err = &'static str;
+
+    // Parse the names used in the SGF RU property, plus a few common spellings
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use self::Rules::*;
+
+        let name: String = s.trim().chars()
+            .filter(|c| c.is_alphanumeric())
+            .flat_map(|c| c.to_lowercase())
+            .collect();
+
+        let rules = match &name[..] {
+            "japanese" | "jp" | "korean" => Japanese,
+            "chinese" | "cn" => Chinese,
+            "aga" | "american" | "bga" | "french" => AGA,
+            "nz" | "newzealand" => NewZealand,
+            "goe" | "ing" => Ing,
+            "tromptaylor" | "tt" => TrompTaylor,
+            _ => return Err("unknown rules"),
+        };
+
+        Ok(Ruleset::new(rules))
+    }
+}
+
+impl Display for Ruleset {
+    // Use the spelling the SGF spec suggests for RU
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        use self::Rules::*;
+
+        let name = match self.rules {
+            Japanese => "Japanese",
+            Chinese => "Chinese",
+            AGA => "AGA",
+            NewZealand => "NZ",
+            Ing => "GOE",
+            TrompTaylor => "Tromp-Taylor",
+        };
+        write!(fmt, "{}", name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+    use super::{Ruleset, Rules, KoRule, Scoring, Compensation};
+
+    #[test] fn parse() {
+        assert_eq!(Ruleset::from_str("Japanese").map(|r| r.rules()), Ok(Rules::Japanese));
+        assert_eq!(Ruleset::from_str("chinese").map(|r| r.rules()), Ok(Rules::Chinese));
+        assert_eq!(Ruleset::from_str("AGA").map(|r| r.rules()), Ok(Rules::AGA));
+        assert_eq!(Ruleset::from_str("NZ").map(|r| r.rules()), Ok(Rules::NewZealand));
+        assert_eq!(Ruleset::from_str("GOE").map(|r| r.rules()), Ok(Rules::Ing));
+        assert_eq!(Ruleset::from_str("Tromp-Taylor").map(|r| r.rules()), Ok(Rules::TrompTaylor));
+        assert!(Ruleset::from_str("Calvinball").is_err());
+    }
+
+    #[test] fn roundtrip() {
+        for r in &[Rules::Japanese, Rules::Chinese, Rules::AGA,
+                   Rules::NewZealand, Rules::Ing, Rules::TrompTaylor] {
+            let rs = Ruleset::new(*r);
+            assert_eq!(Ruleset::from_str(&format!("{}", rs)), Ok(rs));
+        }
+    }
+
+    #[test] fn presets() {
+        let jp = Ruleset::japanese();
+        assert_eq!(jp.ko(), KoRule::Simple);
+        assert!(!jp.suicide());
+        assert_eq!(jp.scoring(), Scoring::Territory);
+
+        let nz = Ruleset::new_zealand();
+        assert!(nz.suicide());
+        assert_eq!(nz.scoring(), Scoring::Area);
+
+        assert_eq!(Compensation::PerStone.points(4), 4.0);
+        assert_eq!(Compensation::PerStoneAfterFirst.points(4), 3.0);
+        assert_eq!(Compensation::PerStoneAfterFirst.points(0), 0.0);
+    }
+}
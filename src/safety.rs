@@ -0,0 +1,353 @@
+// Rough classification of how safe each group on the board is.
+//
+// Unconditional life is exact (Benson's algorithm); everything else is
+// heuristic, looking at the liberties and eye space of each group together
+// with its friendly neighbours, and at which side has more influence around
+// its liberties.
+
+
+use board::{Board, PointSet};
+use group::Group;
+use location::Location;
+use stone::Stone;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash)]
+pub enum Safety {
+    Dead,
+    Weak,
+    Unsettled,
+    Alive,
+    UnconditionallyAlive,
+}
+
+#[derive(Debug, Clone)]
+pub struct Status {
+    group: Group,
+    safety: Safety,
+    liberties: usize,
+    eyes: usize,
+}
+
+impl Status {
+    pub fn group(&self) -> &Group { &self.group }
+    pub fn safety(&self) -> Safety { self.safety }
+
+    /// Liberties of the group and the friendly groups it is connected to
+    pub fn liberties(&self) -> usize { self.liberties }
+
+    /// Eyes (or eye space worth an eye) the connected groups have
+    pub fn eyes(&self) -> usize { self.eyes }
+
+    /// Is the group dead or in danger of dying?
+    pub fn at_risk(&self) -> bool { self.safety <= Safety::Weak }
+}
+
+/// Groups of `colour` which can't be captured even if the opponent is allowed
+/// to play as many moves in a row as they like (Benson's algorithm)
+pub fn unconditional(board: &Board, colour: Stone) -> Vec<Group> {
+    let chains: Vec<Group> = board.groups(colour);
+    let libs: Vec<PointSet> = chains.iter().map(|c| board.liberties(c)).collect();
+
+    // Regions are maximal connected areas not containing `colour` stones. For
+    // each keep its empty points and the chains which border it.
+    let mut seen = PointSet::new();
+    let mut regions = Vec::new();
+    for loc in board.locations() {
+        if board.get(loc) == Some(colour) || !seen.insert(loc) { continue }
+
+        let mut empty = PointSet::new();
+        let mut border = Vec::new();
+        let mut fringe = vec![loc];
+        while let Some(l) = fringe.pop() {
+            if board.get(l).is_none() { let _ = empty.insert(l); }
+            for n in l.neighbours().filter(|n| board.validloc(n)) {
+                if board.get(n) == Some(colour) {
+                    let c = chains.iter().position(|c| c.contains(n)).expect("unknown chain");
+                    if !border.contains(&c) { border.push(c) }
+                } else if seen.insert(n) {
+                    fringe.push(n)
+                }
+            }
+        }
+        regions.push((empty, border));
+    }
+
+    let mut alive = vec![true; chains.len()];
+    let mut healthy = vec![true; regions.len()];
+
+    loop {
+        let mut changed = false;
+
+        // A chain needs two healthy regions whose empty points are all its liberties
+        for c in 0..chains.len() {
+            if !alive[c] { continue }
+            let vital = regions.iter().enumerate()
+                .filter(|&(r, &(ref empty, ref border))|
+                        healthy[r] && border.contains(&c) && empty.is_subset(&libs[c]))
+                .count();
+            if vital < 2 {
+                alive[c] = false;
+                changed = true;
+            }
+        }
+
+        // A region is only healthy while all the chains around it are alive
+        for (r, &(_, ref border)) in regions.iter().enumerate() {
+            if healthy[r] && border.iter().any(|&c| !alive[c]) {
+                healthy[r] = false;
+                changed = true;
+            }
+        }
+
+        if !changed { break }
+    }
+
+    chains.into_iter().zip(alive).filter(|&(_, a)| a).map(|(c, _)| c).collect()
+}
+
+/// Influence of each colour over the board
+///
+/// Each stone radiates influence which falls off with (Manhattan) distance;
+/// Black's is counted as positive and White's as negative.
+#[derive(Debug, Clone)]
+pub struct Influence {
+    size: usize,
+    map: Vec<i32>,
+}
+
+const REACH: usize = 4;
+
+impl Influence {
+    pub fn new(board: &Board) -> Influence {
+        let size = board.size();
+        let mut map = vec![0; size * size];
+
+        for s in board.locations() {
+            let sign = match board.get(s) {
+                Some(Stone::Black) => 1,
+                Some(Stone::White) => -1,
+                None => continue,
+            };
+            for l in board.locations() {
+                let d = distance(&s, &l);
+                if d < REACH {
+                    map[l.col() * size + l.row()] += sign * (REACH - d) as i32;
+                }
+            }
+        }
+
+        Influence { size: size, map: map }
+    }
+
+    pub fn get<L>(&self, loc: L) -> i32
+        where L: AsRef<Location>
+    {
+        let loc = loc.as_ref();
+        self.map[loc.col() * self.size + loc.row()]
+    }
+
+    /// Influence at `loc` from `colour`'s point of view
+    pub fn towards<L>(&self, loc: L, colour: Stone) -> i32
+        where L: AsRef<Location>
+    {
+        match colour {
+            Stone::Black => self.get(loc),
+            Stone::White => -self.get(loc),
+        }
+    }
+}
+
+fn distance(a: &Location, b: &Location) -> usize {
+    let d = |x: usize, y: usize| if x > y { x - y } else { y - x };
+    d(a.col(), b.col()) + d(a.row(), b.row())
+}
+
+// Can a group in atari get out by extending, or by capturing a neighbour?
+fn escapes(board: &Board, group: &Group, lib: Location) -> bool {
+    let colour = group.colour();
+    let mut b = board.clone();
+
+    if b.play(lib, colour) {
+        if let Some(g) = b.group_at(lib) {
+            if b.liberties::<PointSet>(&g).len() >= 2 { return true }
+        }
+    }
+
+    group.neighbours().iter()
+        .filter(|n| board.get(n) == Some(!colour))
+        .filter_map(|n| board.group_at(n))
+        .any(|g| board.liberties::<PointSet>(&g).len() == 1)
+}
+
+/// Classify every group on the board
+pub fn classify(board: &Board) -> Vec<Status> {
+    let influence = Influence::new(board);
+    let regions: Vec<_> = board.regions();
+    let mut status = Vec::new();
+
+    for &colour in &[Stone::Black, Stone::White] {
+        let groups: Vec<Group> = board.groups(colour);
+        let libs: Vec<PointSet> = groups.iter().map(|g| board.liberties(g)).collect();
+        let unconditional = unconditional(board, colour);
+
+        // Friendly groups which share at least two liberties can't be cut
+        // apart, so treat them as a single unit.
+        let mut unit: Vec<usize> = (0..groups.len()).collect();
+        for a in 0..groups.len() {
+            for b in (a + 1)..groups.len() {
+                if libs[a].intersection(&libs[b]).count() >= 2 {
+                    let (ua, ub) = (unit[a], unit[b]);
+                    for u in unit.iter_mut() { if *u == ub { *u = ua } }
+                }
+            }
+        }
+
+        for (i, g) in groups.iter().enumerate() {
+            let mut unitlibs = PointSet::new();
+            for (j, l) in libs.iter().enumerate() {
+                if unit[j] == unit[i] { unitlibs.union_with(l) }
+            }
+
+            // Count eye space next to the unit: a real single point eye or a
+            // small enclosed area is worth an eye, and a large one is enough
+            // for two.
+            let eyes = regions.iter()
+                .filter(|r| r.owner() == Some(colour))
+                .filter(|r| r.locations().any(|l| unitlibs.contains(l)))
+                .map(|r| match r.len() {
+                    1 => if r.locations().all(|l| board.true_eye(l, colour)) { 1 } else { 0 },
+                    2...6 => 1,
+                    _ => 2,
+                })
+                .fold(0, |a, e| a + e);
+
+            let nlibs = unitlibs.len();
+            let outside = unitlibs.iter().map(|l| influence.towards(l, colour)).fold(0, |a, i| a + i);
+            let surrounded = outside < 0;
+
+            let safety =
+                if unconditional.iter().any(|u| u.contains(g.locations().next().unwrap())) {
+                    Safety::UnconditionallyAlive
+                } else if eyes >= 2 {
+                    Safety::Alive
+                } else if libs[i].len() == 1 {
+                    if escapes(board, g, libs[i].iter().next().unwrap()) { Safety::Weak } else { Safety::Dead }
+                } else if eyes == 0 && surrounded && nlibs <= 3 {
+                    Safety::Dead
+                } else if surrounded || nlibs <= 2 {
+                    Safety::Weak
+                } else {
+                    Safety::Unsettled
+                };
+
+            status.push(Status {
+                group: g.clone(),
+                safety: safety,
+                liberties: nlibs,
+                eyes: eyes,
+            })
+        }
+    }
+
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Safety, classify, unconditional};
+    use board::Board;
+    use location::Location;
+    use stone::Stone::{Black, White};
+
+    fn safety(board: &Board, loc: (usize, usize)) -> Safety {
+        classify(board).into_iter()
+            .find(|s| s.group().contains(Location::from(loc)))
+            .map(|s| s.safety())
+            .expect("no group")
+    }
+
+    #[test] fn benson() {
+        // Only one eye
+        let b = Board::from_str("\
+. # # # O . .
+# # # # O . .
+O O O O O . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+").expect("board");
+
+        let black = unconditional(&b, Black);
+        assert_eq!(black.len(), 0);
+
+        let b = Board::from_str("\
+. # . # . . .
+# # # # . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+").expect("board");
+        let black = unconditional(&b, Black);
+        assert_eq!(black.len(), 1);
+        assert_eq!(black[0].len(), 6);
+        assert_eq!(unconditional(&b, White).len(), 0);
+
+        assert_eq!(safety(&b, (1, 5)), Safety::UnconditionallyAlive);
+    }
+
+    #[test] fn statuses() {
+        let b = Board::from_str("\
+. . . . . . . . .
+. O O O O O . . .
+. O # # . O . . .
+. O # . # O . . .
+. O O O O O . . .
+. . . . . . . . .
+. . . . . . # . .
+. . . . . . . . .
+. . . . . . . . .
+").expect("board");
+
+        // Black stones enclosed with a single small eye space are dead
+        assert_eq!(safety(&b, (2, 6)), Safety::Dead);
+        // The lone stone in the open is unsettled
+        assert_eq!(safety(&b, (6, 2)), Safety::Unsettled);
+        // The White wall has plenty of room
+        assert!(safety(&b, (1, 7)) >= Safety::Unsettled);
+    }
+
+    #[test] fn atari() {
+        let b = Board::from_str("\
+. . . . .
+. . O . .
+. O # O .
+. . . . .
+. . . . .
+").expect("board");
+        assert_eq!(safety(&b, (2, 2)), Safety::Weak);
+
+        // Can't extend, but can capture the stone at C4
+        let b = Board::from_str("\
+. . . . .
+. # O # .
+. O # O .
+. O . O .
+. . O . .
+").expect("board");
+        assert_eq!(safety(&b, (2, 2)), Safety::Weak);
+
+        let b = Board::from_str("\
+. . . . .
+. . O . .
+. O # O .
+. O . O .
+. . O . .
+").expect("board");
+        assert_eq!(safety(&b, (2, 2)), Safety::Dead);
+    }
+}
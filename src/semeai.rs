@@ -0,0 +1,267 @@
+// Capturing races between two adjacent groups of opposite colours.
+//
+// Each group's liberties are split into shared liberties, which both groups
+// need, eye liberties inside the group's own eyes, and the remaining outside
+// liberties. The race is then settled by counting, following the usual rules:
+// shared liberties count for neither side unless one of them has an eye (or a
+// bigger eye), and when neither side can fill the shared liberties without
+// putting itself in atari the result is seki.
+//
+// Rather than encode each case of the formulas separately, the race is played
+// out on the liberty counts alone, which covers all the cases (including big
+// eyes and several shared liberties) with the same few rules.
+
+use std::collections::hash_map::HashMap;
+use std::cmp::max;
+
+use board::{Board, PointSet};
+use group::Group;
+use stone::Stone;
+
+/// How a capturing race ends
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    /// The stones of the other colour are captured
+    Win(Stone),
+    Seki,
+}
+
+/// Liberty counts for one side of a race
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub struct Liberties {
+    colour: Stone,
+    outside: usize,
+    eye: usize,
+    eyes: usize,
+}
+
+impl Liberties {
+    pub fn colour(&self) -> Stone { self.colour }
+
+    /// Liberties not shared with the opponent and not inside an eye
+    pub fn outside(&self) -> usize { self.outside }
+
+    /// Moves the opponent needs to fill the group's eyes
+    pub fn eye(&self) -> usize { self.eye }
+
+    /// Number of eyes the group has
+    pub fn eyes(&self) -> usize { self.eyes }
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Semeai {
+    a: Liberties,
+    b: Liberties,
+    shared: usize,
+}
+
+/// Number of moves it takes to fill an eye of `size` points
+///
+/// Big eyes are worth more than their size, because the attacker's stones
+/// inside get captured and have to be played again.
+pub fn eye_liberties(size: usize) -> usize {
+    match size {
+        0...3 => size,
+        n => (n - 1) * (n - 2) / 2 + 2,
+    }
+}
+
+impl Semeai {
+    /// Analyse the race between `a` and `b`, which must be adjacent groups of
+    /// opposite colours
+    pub fn new(board: &Board, a: &Group, b: &Group) -> Option<Semeai> {
+        if a.colour() == b.colour() || !a.touching(b) { return None }
+
+        let alibs: PointSet = board.liberties(a);
+        let blibs: PointSet = board.liberties(b);
+        let shared: PointSet = alibs.intersection(&blibs).collect();
+
+        Some(Semeai {
+            a: Semeai::count(board, a, &alibs, &shared),
+            b: Semeai::count(board, b, &blibs, &shared),
+            shared: shared.len(),
+        })
+    }
+
+    fn count(board: &Board, group: &Group, libs: &PointSet, shared: &PointSet) -> Liberties {
+        let colour = group.colour();
+        let mut eyepoints = PointSet::new();
+        let mut eye = 0;
+        let mut eyes = 0;
+
+        // An eye is an empty region next to the group which only touches the
+        // group's colour. Single points also need to be real eyes.
+        for r in board.regions::<Vec<_>>() {
+            if r.owner() != Some(colour) { continue }
+            if !r.locations().any(|l| libs.contains(l)) { continue }
+            if r.len() == 1 && !r.locations().all(|l| board.true_eye(l, colour)) { continue }
+
+            eye += eye_liberties(r.len());
+            eyes += 1;
+            for l in r.locations() { let _ = eyepoints.insert(l); }
+        }
+
+        let outside = libs.iter()
+            .filter(|l| !shared.contains(*l) && !eyepoints.contains(*l))
+            .count();
+
+        Liberties { colour: colour, outside: outside, eye: eye, eyes: eyes }
+    }
+
+    pub fn shared(&self) -> usize { self.shared }
+
+    /// Liberty counts for the group of `colour`
+    pub fn liberties(&self, colour: Stone) -> &Liberties {
+        if self.a.colour == colour { &self.a } else { &self.b }
+    }
+
+    /// Result of the race when `tomove` plays first
+    pub fn outcome(&self, tomove: Stone) -> Outcome {
+        let (me, them) = if self.a.colour == tomove { (self.a, self.b) } else { (self.b, self.a) };
+        let mut memo = HashMap::new();
+
+        match race(&mut memo, Side::from(&me), Side::from(&them), self.shared, false) {
+            1 => Outcome::Win(me.colour),
+            -1 => Outcome::Win(them.colour),
+            _ => Outcome::Seki,
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+struct Side {
+    outside: usize,
+    eye: usize,
+}
+
+impl<'a> From<&'a Liberties> for Side {
+    fn from(l: &'a Liberties) -> Side { Side { outside: l.outside, eye: l.eye } }
+}
+
+impl Side {
+    fn total(&self, shared: usize) -> usize { self.outside + shared + self.eye }
+}
+
+// Play out the race with `me` to move, returning 1 if `me` captures, -1 if
+// `me` is captured and 0 for seki. Each turn a player can fill one of the
+// opponent's outside liberties, fill a shared liberty as long as that doesn't
+// leave them with no liberties, fill an eye liberty once the outside and
+// shared ones have gone, or pass. Two passes in a row is seki.
+fn race(memo: &mut HashMap<(Side, Side, usize, bool), i32>,
+        me: Side, them: Side, shared: usize, passed: bool) -> i32
+{
+    // Take the last liberty
+    if them.total(shared) <= 1 { return 1 }
+
+    let key = (me, them, shared, passed);
+    if let Some(v) = memo.get(&key) { return *v }
+
+    let mut best = if passed { 0 } else { -race(memo, them, me, shared, true) };
+
+    if them.outside > 0 {
+        let t = Side { outside: them.outside - 1, ..them };
+        best = max(best, -race(memo, t, me, shared, false));
+    }
+
+    if shared > 0 && me.total(shared) > 1 {
+        best = max(best, -race(memo, them, me, shared - 1, false));
+    }
+
+    if them.outside == 0 && shared == 0 && them.eye > 0 {
+        let t = Side { eye: them.eye - 1, ..them };
+        best = max(best, -race(memo, t, me, shared, false));
+    }
+
+    let _ = memo.insert(key, best);
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Semeai, Outcome, eye_liberties};
+    use board::Board;
+    use location::Location;
+    use stone::Stone::{Black, White};
+
+    fn semeai(pos: &str, a: (usize, usize), b: (usize, usize)) -> Semeai {
+        let board = Board::from_str(pos).expect("board");
+        let a = board.group_at(Location::from(a)).expect("group a");
+        let b = board.group_at(Location::from(b)).expect("group b");
+        Semeai::new(&board, &a, &b).expect("semeai")
+    }
+
+    #[test] fn eyelibs() {
+        let v: Vec<_> = (1..7).map(eye_liberties).collect();
+        assert_eq!(v, vec![1, 2, 3, 5, 8, 12]);
+    }
+
+    #[test] fn not_a_race() {
+        let board = Board::from_str("\
+# . O
+# . O
+. . .
+").expect("board");
+        let a = board.group_at(Location::new(0, 2)).unwrap();
+        let b = board.group_at(Location::new(2, 2)).unwrap();
+        assert_eq!(Semeai::new(&board, &a, &b), None);
+    }
+
+    #[test] fn no_eyes() {
+        // Black has three outside liberties against White's two
+        let s = semeai("\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+O O O O # # #
+O # # # O O #
+. . . . . . .
+", (1, 1), (4, 1));
+
+        assert_eq!(s.shared(), 0);
+        assert_eq!(s.liberties(Black).outside(), 3);
+        assert_eq!(s.liberties(White).outside(), 2);
+        assert_eq!(s.outcome(Black), Outcome::Win(Black));
+        assert_eq!(s.outcome(White), Outcome::Win(Black));
+    }
+
+    #[test] fn seki() {
+        // Black and White each have one outside liberty and share two more
+        let s = semeai("\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. O O # . . .
+O # # O # . .
+O # . O # . .
+. # . O . # .
+", (1, 0), (3, 0));
+
+        assert_eq!(s.shared(), 2);
+        assert_eq!(s.liberties(Black).outside(), 1);
+        assert_eq!(s.liberties(White).outside(), 1);
+        assert_eq!(s.outcome(Black), Outcome::Seki);
+        assert_eq!(s.outcome(White), Outcome::Seki);
+    }
+
+    #[test] fn eye_beats_no_eye() {
+        // Like the seki, but White has an eye so the shared liberties count for White
+        let s = semeai("\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. O O # # . .
+O # # O O # .
+O # . O . O #
+. # . O O O #
+", (1, 0), (3, 0));
+
+        assert_eq!(s.liberties(White).eyes(), 1);
+        assert_eq!(s.liberties(White).eye(), 1);
+        assert_eq!(s.liberties(White).outside(), 0);
+        assert_eq!(s.outcome(Black), Outcome::Win(White));
+        assert_eq!(s.outcome(White), Outcome::Win(White));
+    }
+}
@@ -4,9 +4,11 @@ use nom::IResult;
 mod parser;
 pub mod node;
 pub mod property;
+pub mod writer;
 
 pub use self::property::Property;
 pub use self::node::Node;
+pub use self::writer::Writer;
 pub use self::parser::collection;
 
 pub type Result<T> = result::Result<T, Error>;
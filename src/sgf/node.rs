@@ -1,9 +1,11 @@
 use std::collections::hash_map::{self, HashMap};
+use std::fmt::{self, Display};
 use std::ops::{Index, Range, RangeFrom, RangeTo, RangeFull};
 
 use sgf::property::{self, Property};
+use sgf::writer::Writer;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Node {
     props: HashMap<String, Property>,
     children: Vec<Node>,
@@ -89,3 +91,14 @@ impl Index<RangeFull> for Node {
         &self.children[..]
     }
 }
+
+impl Display for Node {
+    // The game tree rooted here, in SGF, all on one line
+    fn fmt(&self, fmt: &mut fmt::Formatter) -> fmt::Result {
+        let mut out = Vec::new();
+        let mut writer = Writer::new();
+        writer.set_width(None);
+        try!(writer.write_tree(&mut out, self).map_err(|_| fmt::Error));
+        write!(fmt, "{}", String::from_utf8_lossy(&out))
+    }
+}
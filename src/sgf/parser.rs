@@ -26,18 +26,22 @@ use super::{Node, Property};
 named!(pub collection<Vec<Node> >, many1!(gametree));
 
 named!(gametree<Node>,
-    chain!(lparen ~ mut node:sequence ~ variants:many0!(gametree) ~ rparen, || {
-        for v in variants {
-            node.addchild(v)
-        };
-        node
+    chain!(lparen ~ nodes:sequence ~ variants:many0!(gametree) ~ rparen, || {
+        link(nodes, variants)
     }));
 
-named!(sequence<Node>, map!(many1!(node), |nodes: Vec<Node>| {
-        let mut it = nodes.into_iter().rev();
-        let last = it.next().unwrap();
-        it.fold(last, |s, mut n| { n.addchild(s); n })
-    }));
+named!(sequence<Vec<Node> >, many1!(node));
+
+// Chain a sequence of nodes together, each the only child of the one before,
+// with the variations branching off the last
+fn link(nodes: Vec<Node>, variants: Vec<Node>) -> Node {
+    let mut it = nodes.into_iter().rev();
+    let mut last = it.next().unwrap();
+    for v in variants {
+        last.addchild(v)
+    };
+    it.fold(last, |s, mut n| { n.addchild(s); n })
+}
 
 named!(node<Node>, chain!(semicolon ~ props:many0!(property), || {
         let mut n = Node::new();
@@ -208,4 +212,22 @@ mod test {
             other => panic!("other: {:?}", other),
         }
     }
+
+    #[test] fn t_variations() {
+        // A game tree's variations follow on from the last node of its
+        // sequence, not the first
+        match gametree(b"(;W[nf] ;B[qf] ;W[lc] ;B[od] (;W[oe] ;B[md]) (;W[ld] ;B[ne] ;W[me]) )") {
+            Done(b"", root) => {
+                assert_eq!(root.len(), 1);
+                assert_eq!(root[0].len(), 1);
+                assert_eq!(root[0][0].len(), 1);
+                let od = &root[0][0][0];
+                assert!(od.prop("B").is_some());
+                assert_eq!(od.len(), 2);
+                assert!(od[0].prop("W").is_some() && od[0].len() == 1);
+                assert!(od[1].prop("W").is_some() && od[1][0].len() == 1);
+            },
+            other => panic!("other: {:?}", other),
+        }
+    }
 }
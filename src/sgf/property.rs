@@ -6,8 +6,9 @@ use std::result;
 use super::{Result, Error};
 
 use ::stone::Stone;
+use ::rules::Ruleset;
 
-#[derive(Debug, Clone, Hash)]
+#[derive(Debug, Clone, Hash, Eq, PartialEq)]
 pub struct Property {
     id: String,
     raw: Vec<Vec<u8>>,
@@ -52,6 +53,9 @@ impl Property {
     }
 
     pub fn len(&self) -> usize { self.raw.len() }
+
+    /// The values as they are in the file, with their escapes
+    pub fn raw(&self) -> &[Vec<u8>] { &self.raw }
 }
 
 fn fold_res<R, T, E>(vr: R) -> result::Result<Vec<T>, E>
@@ -84,6 +88,7 @@ pub enum Value {
     Text(Text),
 
     GoMove(go::Move),
+    Rules(Ruleset),
 
     Compose(Box<Value>, Box<Value>),
     Raw(Vec<u8>),
@@ -117,6 +122,10 @@ impl Value {
     pub fn gomove(&self) -> Option<&go::Move> {
         if let &Value::GoMove(ref n) = self { Some(n) } else { None }
     }
+
+    pub fn rules(&self) -> Option<&Ruleset> {
+        if let &Value::Rules(ref n) = self { Some(n) } else { None }
+    }
 }
 
 impl From<Number> for Value {
@@ -147,6 +156,10 @@ impl From<go::Move> for Value {
     fn from(v: go::Move) -> Self { Value::GoMove(v) }
 }
 
+impl From<Ruleset> for Value {
+    fn from(v: Ruleset) -> Self { Value::Rules(v) }
+}
+
 impl<L, R> From<Compose<L, R>> for Value
     where L: Into<Value>, R: Into<Value>
 {
@@ -250,16 +263,17 @@ pub struct SimpleText(String);
 
 impl ValueParse for SimpleText {
     fn parse(raw: &[u8]) -> Result<Value> {
-        let mut s = String::new();
-        for c in raw {
-            match *c as char {
-                '\\' => (),
-                '\n' | '\t' | '\r' => s.push(' '),
-                c => s.push(c),
+        let mut s = Vec::new();
+        let mut quote = false;
+        for &c in raw {
+            match c {
+                b'\\' if !quote => quote = true,
+                b'\n' | b'\t' | b'\r' => { quote = false; s.push(b' ') },
+                c => { quote = false; s.push(c) },
             }
         }
 
-        Ok(Value::from(SimpleText(s)))
+        Ok(Value::from(SimpleText(String::from_utf8_lossy(&s).into_owned())))
     }
 }
 
@@ -277,20 +291,20 @@ pub struct Text(String);
 
 impl ValueParse for Text {
     fn parse(raw: &[u8]) -> Result<Value> {
-        let mut s = String::new();
+        let mut s = Vec::new();
         let mut quote = false;
-        for c in raw {
+        for &c in raw {
             // "Following chars have to be escaped, when used in Text: "]", "\" and ":" (only if used in compose data type)."
             // How do we know if we're in a compose?
-            match *c as char {
-                '\\' if !quote => quote = true,
-                '\n' if quote => { quote = false; s.push(' ') },
-                '\t' | '\r' => s.push(' '),
+            match c {
+                b'\\' if !quote => quote = true,
+                b'\n' if quote => { quote = false; s.push(b' ') },
+                b'\t' | b'\r' => s.push(b' '),
                 c => { quote = false; s.push(c) },
             }
         }
 
-        Ok(Value::from(Text(s)))
+        Ok(Value::from(Text(String::from_utf8_lossy(&s).into_owned())))
     }
 }
 
@@ -302,6 +316,16 @@ impl<'a> Into<String> for &'a Text {
     fn into(self) -> String { self.0.clone() }
 }
 
+impl ValueParse for Ruleset {
+    fn parse(raw: &[u8]) -> Result<Value> {
+        // RU is really free text, so keep the text of rules we don't know about
+        match str::from_utf8(raw).ok().and_then(|s| Ruleset::from_str(s).ok()) {
+            Some(r) => Ok(Value::from(r)),
+            None => SimpleText::parse(raw),
+        }
+    }
+}
+
 #[derive(Debug, Clone, Eq, PartialEq)]
 pub struct Compose<L, R>(Box<L>, Box<R>);
 
@@ -419,7 +443,7 @@ lazy_static!{
             Detail("PW", "Player White", GameInfo, false, SimpleText::parse /* simpletext */),
             Detail("RE", "Result", GameInfo, false, SimpleText::parse /* simpletext */),
             Detail("RO", "Round", GameInfo, false, SimpleText::parse /* simpletext */),
-            Detail("RU", "Rules", GameInfo, false, SimpleText::parse /* simpletext */),
+            Detail("RU", "Rules", GameInfo, false, Ruleset::parse /* simpletext */),
             Detail("SE", "Markup", None, false, go::Point::parse /* point */),
             Detail("SL", "Selected", None, false, go::Point::parse /* list of point */),
             Detail("SO", "Source", GameInfo, false, SimpleText::parse /* simpletext */),
@@ -0,0 +1,215 @@
+// Writing game trees out as SGF.
+//
+// Property values are written as they are held, which is as they were in the
+// file they were read from, escapes and all, so parsing the output gives the
+// same tree back. Values made up from text need escaping first: `]` and `\`
+// always take a backslash, and `:` does too in either half of a composed
+// value, such as a label.
+//
+// Lines are only ever broken between properties, nodes and game trees, where
+// SGF allows white space, and never inside a value, where a line break would
+// be part of the text. A property longer than the width gets a line to
+// itself, and a node's `;`, and any `(` before it, stay with its first
+// property.
+
+use std::io::{self, Write};
+use std::mem;
+
+use super::Node;
+
+/// Escape `text` for an SGF property value
+pub fn escape(text: &str) -> Vec<u8> {
+    escape_chars(text, b"]\\")
+}
+
+/// Escape `text` for one half of a composed value, like "dd:a label"
+pub fn escape_compose(text: &str) -> Vec<u8> {
+    escape_chars(text, b"]\\:")
+}
+
+fn escape_chars(text: &str, special: &[u8]) -> Vec<u8> {
+    let mut v = Vec::with_capacity(text.len());
+    for &b in text.as_bytes() {
+        if special.contains(&b) { v.push(b'\\') }
+        v.push(b);
+    }
+    v
+}
+
+/// Writes collections of game trees in SGF
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub struct Writer {
+    width: Option<usize>,
+}
+
+impl Writer {
+    /// Writer which wraps lines at 80 columns
+    pub fn new() -> Writer {
+        Writer { width: Some(80) }
+    }
+
+    /// Start a new line before anything which would take a line past `width`
+    /// columns (None to keep each game tree on one line)
+    pub fn set_width(&mut self, width: Option<usize>) { self.width = width }
+
+    /// Write each of the game trees in `collection`, each ending with a new
+    /// line
+    pub fn write<W: Write>(&self, out: &mut W, collection: &[Node]) -> io::Result<()> {
+        for root in collection {
+            try!(self.write_tree(out, root));
+            try!(out.write_all(b"\n"));
+        }
+        Ok(())
+    }
+
+    /// Write the game tree rooted at `root`
+    pub fn write_tree<W: Write>(&self, out: &mut W, root: &Node) -> io::Result<()> {
+        let mut line = Line { out: out, width: self.width, col: 0, open: Vec::new() };
+        line.tree(root)
+    }
+
+    /// The collection in SGF
+    pub fn to_vec(&self, collection: &[Node]) -> Vec<u8> {
+        let mut v = Vec::new();
+        self.write(&mut v, collection).expect("writing to a Vec can't fail");
+        v
+    }
+}
+
+// Output, and how far along the current line it's got
+struct Line<'a, W: Write + 'a> {
+    out: &'a mut W,
+    width: Option<usize>,
+    col: usize,
+    // Opening brackets waiting to go out with the next token
+    open: Vec<u8>,
+}
+
+impl<'a, W: Write> Line<'a, W> {
+    // Write `token`, on a new line if it won't fit on this one
+    fn put(&mut self, token: &[u8]) -> io::Result<()> {
+        let mut joined = mem::replace(&mut self.open, Vec::new());
+        joined.extend(token);
+        let token = &joined[..];
+
+        let first = token.iter().position(|&b| b == b'\n').unwrap_or(token.len());
+        if self.width.map_or(false, |w| self.col > 0 && self.col + first > w) {
+            try!(self.out.write_all(b"\n"));
+            self.col = 0;
+        }
+
+        try!(self.out.write_all(token));
+        self.col = match token.iter().rposition(|&b| b == b'\n') {
+            Some(i) => token.len() - i - 1,
+            None => self.col + token.len(),
+        };
+        Ok(())
+    }
+
+    fn tree(&mut self, node: &Node) -> io::Result<()> {
+        self.open.push(b'(');
+        try!(self.sequence(node));
+        self.put(b")")
+    }
+
+    // Write `node` and the rest of its game tree; a single child carries on
+    // the same sequence, and several become variations
+    fn sequence(&mut self, node: &Node) -> io::Result<()> {
+        let mut props: Vec<_> = node.properties().collect();
+        props.sort_by(|a, b| a.id().cmp(b.id()));
+
+        let mut token = b";".to_vec();
+        for (i, p) in props.into_iter().enumerate() {
+            if i > 0 {
+                try!(self.put(&token));
+                token.clear();
+            }
+            token.extend(p.id().as_bytes());
+            for v in p.raw() {
+                token.push(b'[');
+                token.extend(v);
+                token.push(b']');
+            }
+        }
+        try!(self.put(&token));
+
+        match node.len() {
+            0 => Ok(()),
+            1 => self.sequence(&node[0]),
+            _ => {
+                for child in &node[..] {
+                    try!(self.tree(child));
+                }
+                Ok(())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str;
+
+    use super::{Writer, escape, escape_compose};
+    use sgf::{self, Node, Property};
+    use sgf::property::Value;
+
+    const GAME: &'static [u8] = b"(;FF[4]GM[1]SZ[9]PB[Black \\] \\\\ player]C[A comment\n\
+                                  over two lines];B[ee]C[Tengen];W[cc](;B[gc];W[gg]LB[ee:a\\:b][gc:2])\
+                                  (;B[cg]TR[cc][gg]))(;FF[4]SZ[5];B[cc])";
+
+    #[test] fn escaping() {
+        assert_eq!(escape("plain"), b"plain");
+        assert_eq!(escape("a]b\\c:d"), b"a\\]b\\\\c:d");
+        assert_eq!(escape_compose("a]b\\c:d"), b"a\\]b\\\\c\\:d");
+
+        // Escaped text reads back as it was
+        for &text in &["the end]", "back\\slash", "\\]\\", "J\u{f6}rg"] {
+            let prop = Property::new("GN".to_string(), vec![escape(text)]);
+            match prop.value() {
+                Ok(Value::SimpleText(ref t)) => { let t: &str = t.into(); assert_eq!(t, text) },
+                other => panic!("{:?}", other),
+            }
+        }
+    }
+
+    #[test] fn round_trip() {
+        let coll = sgf::parser(GAME).expect("parse");
+        assert_eq!(coll.len(), 2);
+
+        let mut writer = Writer::new();
+        for &width in &[None, Some(80), Some(20), Some(1)] {
+            writer.set_width(width);
+            let out = writer.to_vec(&coll);
+            assert_eq!(sgf::parser(&out).expect("reparse"), coll, "width {:?}", width);
+        }
+
+        writer.set_width(None);
+        let out = writer.to_vec(&coll);
+        assert_eq!(str::from_utf8(&out).unwrap(),
+                   "(;C[A comment\nover two lines]FF[4]GM[1]PB[Black \\] \\\\ player]SZ[9];B[ee]C[Tengen];W[cc]\
+                    (;B[gc];LB[ee:a\\:b][gc:2]W[gg])(;B[cg]TR[cc][gg]))\n(;FF[4]SZ[5];B[cc])\n");
+    }
+
+    #[test] fn wrapping() {
+        let mut root = Node::new();
+        root.addprop(Property::new("GN".to_string(), vec![vec![b'x'; 30]]));
+        root.addprop(Property::new("SZ".to_string(), vec![b"9".to_vec()]));
+        let mut node = Node::new();
+        node.addprop(Property::new("B".to_string(), vec![b"aa".to_vec()]));
+        root.addchild(node);
+
+        let mut writer = Writer::new();
+        writer.set_width(Some(20));
+        let out = writer.to_vec(&[root.clone()]);
+        let out = str::from_utf8(&out).unwrap();
+        assert_eq!(out, format!("(;GN[{}]\nSZ[9];B[aa])\n", "x".repeat(30)));
+
+        // Only the overlong property goes over the width
+        assert!(out.lines().filter(|l| l.len() > 20).count() == 1);
+
+        writer.set_width(Some(80));
+        assert_eq!(writer.to_vec(&[root.clone()]), format!("(;GN[{}]SZ[9];B[aa])\n", "x".repeat(30)).into_bytes());
+        assert_eq!(root.to_string(), format!("(;GN[{}]SZ[9];B[aa])", "x".repeat(30)));
+    }
+}
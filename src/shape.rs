@@ -0,0 +1,239 @@
+// Recognise named shapes made by a stone and the stones around it.
+//
+// Each shape is drawn as a small picture which is tried in every rotation and
+// reflection, with the stone being looked at in the place of each of the
+// picture's own stones in turn.
+
+use std::fmt::{self, Display};
+
+use board::Board;
+use location::Location;
+use pattern::transform;
+use stone::Stone;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq, Hash)]
+pub enum Name {
+    EmptyTriangle,
+    TigersMouth,
+    BambooJoint,
+    TableShape,
+    Ponnuki,
+    Hane,
+    Crosscut,
+    Diagonal,
+    OnePointJump,
+    TwoPointJump,
+    KnightsMove,
+    LargeKnightsMove,
+}
+
+impl Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use self::Name::*;
+
+        let s = match *self {
+            EmptyTriangle => "empty triangle",
+            TigersMouth => "tiger's mouth",
+            BambooJoint => "bamboo joint",
+            TableShape => "table shape",
+            Ponnuki => "ponnuki",
+            Hane => "hane",
+            Crosscut => "crosscut",
+            Diagonal => "diagonal",
+            OnePointJump => "one-point jump",
+            TwoPointJump => "two-point jump",
+            KnightsMove => "knight's move",
+            LargeKnightsMove => "large knight's move",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// A shape found on the board, and the stones of the mover's colour which
+/// make it up
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Annotation {
+    name: Name,
+    stones: Vec<Location>,
+}
+
+impl Annotation {
+    pub fn name(&self) -> Name { self.name }
+    pub fn stones(&self) -> &[Location] { &self.stones }
+}
+
+impl Display for Annotation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{}:", self.name));
+        for s in &self.stones {
+            try!(write!(f, " {}", s))
+        }
+        Ok(())
+    }
+}
+
+// Pictures of the shapes: X is one of the mover's stones, O an opponent's, .
+// an empty point, x a point on the board without one of the mover's stones,
+// and ? anything at all, including off the board.
+lazy_static!{
+    static ref SHAPES: Vec<(Name, Vec<((i32, i32), char)>)> = {
+        use self::Name::*;
+
+        vec![
+            (EmptyTriangle, "XX\nX."),
+            (TigersMouth, "?X?\nX.X\n?x?"),
+            (BambooJoint, "XX\n..\nXX"),
+            (TableShape, "XX\n..\nX."),
+            (Ponnuki, "?X?\nX.X\n?X?"),
+            (Hane, "XO\n.X"),
+            (Crosscut, "XO\nOX"),
+            (Diagonal, "X.\n.X"),
+            (OnePointJump, "X.X"),
+            (TwoPointJump, "X..X"),
+            (KnightsMove, "X?\n..\n?X"),
+            (LargeKnightsMove, "X?\n..\n..\n?X"),
+        ].into_iter().map(|(name, pic)| {
+            let cells = pic.lines().enumerate()
+                .flat_map(|(y, line)| line.chars().enumerate().map(move |(x, c)| ((x as i32, y as i32), c)))
+                .filter(|&(_, c)| c != '?')
+                .collect();
+            (name, cells)
+        }).collect()
+    };
+}
+
+// Does the picture match with its cell `anchor` on `loc`, under symmetry `t`?
+// Returns the mover's stones if so.
+fn matches(board: &Board, loc: &Location, colour: Stone,
+           cells: &[((i32, i32), char)], anchor: (i32, i32), t: usize) -> Option<Vec<Location>>
+{
+    let size = board.size() as i32;
+    let mut stones = Vec::new();
+
+    for &((x, y), c) in cells {
+        let (dx, dy) = transform(t, (x - anchor.0, y - anchor.1));
+        let (col, row) = (loc.col() as i32 + dx, loc.row() as i32 + dy);
+        if col < 0 || row < 0 || col >= size || row >= size { return None }
+
+        let l = Location::new(col as usize, row as usize);
+        let ok = match (c, board.get(l)) {
+            ('X', Some(s)) => s == colour,
+            ('O', Some(s)) => s == !colour,
+            ('.', None) => true,
+            ('x', s) => s != Some(colour),
+            _ => false,
+        };
+        if !ok { return None }
+        if c == 'X' { stones.push(l) }
+    }
+
+    Some(stones)
+}
+
+/// Named shapes which the stone at `loc` is part of
+pub fn shapes(board: &Board, loc: Location) -> Vec<Annotation> {
+    let colour = match board.get(loc) { Some(c) => c, None => return Vec::new() };
+    let mut found: Vec<Annotation> = Vec::new();
+
+    for &(name, ref cells) in SHAPES.iter() {
+        for &(anchor, _) in cells.iter().filter(|&&(_, c)| c == 'X') {
+            for t in 0..8 {
+                if let Some(mut stones) = matches(board, &loc, colour, cells, anchor, t) {
+                    stones.sort_by_key(|l| (l.row(), l.col()));
+                    let a = Annotation { name: name, stones: stones };
+                    if !found.contains(&a) { found.push(a) }
+                }
+            }
+        }
+    }
+
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Name, shapes};
+    use board::Board;
+    use location::Location;
+
+    fn loc(s: &str) -> Location { Location::from_str(s).unwrap() }
+
+    fn names(board: &Board, at: &str) -> Vec<Name> {
+        let mut n: Vec<_> = shapes(board, loc(at)).iter().map(|a| a.name()).collect();
+        n.dedup();
+        n
+    }
+
+    #[test] fn jumps() {
+        let b = Board::from_str("\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. # . # . . .
+. . . . . . .
+. . # . . . .
+. . . . . . #
+").expect("board");
+
+        assert_eq!(names(&b, "B4"), vec![Name::OnePointJump, Name::KnightsMove]);
+        let all = shapes(&b, loc("C2"));
+        assert_eq!(all.len(), 2);
+        assert!(all.iter().any(|a| a.name() == Name::KnightsMove && a.stones() == &[loc("C2"), loc("B4")]));
+        assert!(all.iter().any(|a| a.name() == Name::KnightsMove && a.stones() == &[loc("C2"), loc("D4")]));
+        assert!(names(&b, "G1").is_empty());
+        assert!(names(&b, "A1").is_empty());
+    }
+
+    #[test] fn contact() {
+        let b = Board::from_str("\
+. . . .
+. # # .
+. # . .
+. . . .
+").expect("board");
+        assert_eq!(names(&b, "B2"), vec![Name::EmptyTriangle]);
+
+        let b = Board::from_str("\
+. . . . .
+. . . . .
+. # O . .
+# . # . .
+. . . . .
+").expect("board");
+
+        let n = names(&b, "C2");
+        assert!(n.contains(&Name::Hane));
+        assert!(n.contains(&Name::TigersMouth));
+        assert!(!n.contains(&Name::EmptyTriangle));
+
+        let tiger = shapes(&b, loc("C2")).into_iter().find(|a| a.name() == Name::TigersMouth).unwrap();
+        assert_eq!(tiger.stones(), &[loc("A2"), loc("C2"), loc("B3")]);
+        assert_eq!(format!("{}", tiger), "tiger's mouth: A2 C2 B3");
+    }
+
+    #[test] fn capture_shapes() {
+        let b = Board::from_str("\
+. . . . . .
+. . # . . .
+. # . # . O
+. . # . O .
+. . . . . O
+. . . . . .
+").expect("board");
+
+        assert!(names(&b, "C5").contains(&Name::Ponnuki));
+        assert!(names(&b, "F4").contains(&Name::Diagonal));
+
+        let b = Board::from_str("\
+. . . . .
+. # # . .
+. . . . .
+. # # . .
+. . . . .
+").expect("board");
+        assert!(names(&b, "B2").contains(&Name::BambooJoint));
+        assert!(names(&b, "B2").contains(&Name::OnePointJump));
+    }
+}
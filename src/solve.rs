@@ -0,0 +1,310 @@
+// Exact solver for tiny boards.
+//
+// Searches the whole game tree with alpha-beta, using a transposition table
+// keyed on the position hash, to find the result of perfect play under the
+// game's rules and komi. The value of a position is the final margin for the
+// side to move, counted as `Game::score` does once both sides pass.
+//
+// Moves which fill one of the mover's own real eyes are never tried, which
+// keeps the games short.
+//
+// Rules which allow positions to repeat (simple ko) could otherwise cycle
+// forever, so a line which returns to a position already on it is stopped
+// and scored as no result, a draw.
+//
+// Values which were cut short by the depth limit or a repetition, or which
+// had moves ruled out by ko, are kept in the transposition table along with
+// how deep they were searched, and only used again for positions which have
+// no more than that left to search.
+
+use std::collections::hash_map::HashMap;
+use std::{f32, usize};
+
+use game::{Error, Game, Move};
+use location::Location;
+use rules::Scoring;
+use stone::Stone;
+
+/// Largest board which can reasonably be solved
+pub const MAX_SIZE: usize = 5;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Bound {
+    Exact,
+    Lower,
+    Upper,
+}
+
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    value: f32,
+    bound: Bound,
+    best: Move,
+    // Moves left to search when the value was found, or usize::MAX if the
+    // search reached the end of every line
+    depth: usize,
+}
+
+// Position hash, side to move, whether the last move was a pass, and (for
+// territory scoring) prisoners taken by Black less those taken by White
+type Key = (u64, Stone, bool, isize);
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    margin: f32,
+    line: Vec<(Stone, Move)>,
+}
+
+impl Solution {
+    /// Black's winning margin with perfect play (negative if White wins)
+    pub fn margin(&self) -> f32 { self.margin }
+
+    pub fn winner(&self) -> Option<Stone> {
+        if self.margin > 0.0 { Some(Stone::Black) }
+        else if self.margin < 0.0 { Some(Stone::White) }
+        else { None }
+    }
+
+    /// Optimal play for both sides, to the end of the game
+    pub fn line(&self) -> &[(Stone, Move)] { &self.line }
+}
+
+#[derive(Debug)]
+pub struct Solver {
+    game: Game,
+    table: HashMap<Key, Entry>,
+    path: Vec<u64>,
+    max_depth: usize,
+    nodes: usize,
+}
+
+impl Solver {
+    /// Solver for the game as it stands, or None if the board is too big
+    pub fn new(game: Game) -> Option<Solver> {
+        if game.size() > MAX_SIZE { return None }
+
+        let points = game.size() * game.size();
+        Some(Solver {
+            game: game,
+            table: HashMap::new(),
+            path: Vec::new(),
+            max_depth: 4 * points + 4,
+            nodes: 0,
+        })
+    }
+
+    /// Longest line to search; positions beyond it are scored as they stand
+    pub fn set_max_depth(&mut self, depth: usize) { self.max_depth = depth }
+
+    /// Number of positions searched so far
+    pub fn nodes(&self) -> usize { self.nodes }
+
+    pub fn solve(&mut self) -> Solution {
+        self.path = vec![self.game.board().zobrist()];
+        let depth = self.max_depth;
+        let (value, _) = self.search(-f32::INFINITY, f32::INFINITY, depth);
+
+        let margin = match self.game.tomove() {
+            Stone::Black => value,
+            Stone::White => -value,
+        };
+
+        Solution { margin: margin, line: self.line() }
+    }
+
+    fn key(&self) -> Key {
+        let g = &self.game;
+        let prisoners = match g.rules().scoring() {
+            Scoring::Territory => g.captures(Stone::Black) as isize - g.captures(Stone::White) as isize,
+            Scoring::Area => 0,
+        };
+        (g.board().zobrist(), g.tomove(), g.passes() > 0, prisoners)
+    }
+
+    // Final margin from the point of view of the side to move
+    fn value(&self) -> f32 {
+        let margin = self.game.score().margin();
+        match self.game.tomove() {
+            Stone::Black => margin,
+            Stone::White => -margin,
+        }
+    }
+
+    // Points nearest the centre first, then passing
+    fn moves(&self, first: Option<Move>) -> Vec<Move> {
+        let size = self.game.size();
+        let centre = |l: &Location| {
+            let d = |x: usize| (2 * x as isize - (size as isize - 1)).abs();
+            d(l.col()) + d(l.row())
+        };
+
+        let board = self.game.board();
+        let tomove = self.game.tomove();
+        let mut plays: Vec<_> = board.locations()
+            .filter(|l| board.get(l).is_none())
+            .filter(|l| !(board.eyelike(l, tomove) && board.true_eye(l, tomove)))
+            .collect();
+        plays.sort_by_key(|l| centre(l));
+
+        let mut moves: Vec<_> = first.into_iter().collect();
+        moves.extend(plays.into_iter().map(Move::Play).chain(Some(Move::Pass)).filter(|&m| Some(m) != first));
+        moves
+    }
+
+    // Value of the position for the side to move searching `depth` moves
+    // ahead, and whether any line was cut short
+    fn search(&mut self, mut alpha: f32, mut beta: f32, depth: usize) -> (f32, bool) {
+        self.nodes += 1;
+
+        if self.game.over() { return (self.value(), false) }
+        if depth == 0 { return (self.value(), true) }
+
+        let key = self.key();
+        let mut first = None;
+        if let Some(e) = self.table.get(&key) {
+            first = Some(e.best);
+            if e.depth >= depth {
+                let limited = e.depth != usize::MAX;
+                match e.bound {
+                    Bound::Exact => return (e.value, limited),
+                    Bound::Lower => if e.value > alpha { alpha = e.value },
+                    Bound::Upper => if e.value < beta { beta = e.value },
+                }
+                if alpha >= beta { return (e.value, limited) }
+            }
+        }
+
+        let start = alpha;
+        let tomove = self.game.tomove();
+        let mut best = -f32::INFINITY;
+        let mut bestmove = Move::Pass;
+        let mut limited = false;
+
+        for mv in self.moves(first) {
+            match self.game.play(tomove, mv) {
+                Ok(()) => (),
+                Err(Error::Ko) => { limited = true; continue },
+                Err(_) => continue,
+            }
+
+            let z = self.game.board().zobrist();
+            let v = if mv != Move::Pass && self.path.contains(&z) {
+                limited = true;
+                0.0
+            } else {
+                self.path.push(z);
+                let (v, l) = self.search(-beta, -alpha, depth - 1);
+                let _ = self.path.pop();
+                limited |= l;
+                -v
+            };
+            let _ = self.game.undo();
+
+            if v > best {
+                best = v;
+                bestmove = mv;
+            }
+            if best > alpha { alpha = best }
+            if alpha >= beta { break }
+        }
+
+        let bound = if best <= start { Bound::Upper } else if best >= beta { Bound::Lower } else { Bound::Exact };
+        let _ = self.table.insert(key, Entry {
+            value: best,
+            bound: bound,
+            best: bestmove,
+            depth: if limited { depth } else { usize::MAX },
+        });
+
+        (best, limited)
+    }
+
+    // Follow the best moves from the starting position
+    fn line(&mut self) -> Vec<(Stone, Move)> {
+        let mut line = Vec::new();
+        let mut seen = vec![self.game.board().zobrist()];
+
+        while !self.game.over() && line.len() < self.max_depth {
+            let tomove = self.game.tomove();
+            let mv = match self.table.get(&self.key()) {
+                Some(e) => e.best,
+                None => break,
+            };
+            if self.game.play(tomove, mv).is_err() { break }
+            line.push((tomove, mv));
+
+            let z = self.game.board().zobrist();
+            if mv != Move::Pass && seen.contains(&z) { break }
+            seen.push(z);
+        }
+
+        for _ in 0..line.len() { let _ = self.game.undo(); }
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Solver;
+    use game::{Game, Move};
+    use location::Location;
+    use rules::Ruleset;
+    use stone::Stone::{Black, White};
+    use std::usize;
+
+    #[test] fn too_big() {
+        assert!(Solver::new(Game::new(9, Ruleset::chinese())).is_none());
+    }
+
+    #[test] fn three_by_three() {
+        let mut game = Game::new(3, Ruleset::chinese());
+        game.set_komi(0.0);
+
+        let mut s = Solver::new(game).unwrap();
+        let sol = s.solve();
+
+        // Black takes the centre and the whole board
+        assert_eq!(sol.margin(), 9.0);
+        assert_eq!(sol.winner(), Some(Black));
+        assert_eq!(sol.line()[0], (Black, Move::Play(Location::new(1, 1))));
+        assert!(s.nodes() > 0);
+    }
+
+    #[test] fn two_by_two() {
+        let mut game = Game::new(2, Ruleset::tromp_taylor());
+        game.set_komi(0.0);
+
+        let sol = Solver::new(game).unwrap().solve();
+        assert_eq!(sol.margin(), 1.0);
+        assert_eq!(sol.line().last().map(|&(_, m)| m), Some(Move::Pass));
+    }
+
+    #[test] fn komi() {
+        let mut game = Game::new(3, Ruleset::chinese());
+        game.set_komi(9.5);
+        assert_eq!(Solver::new(game).unwrap().solve().winner(), Some(White));
+    }
+
+    #[test] fn ko() {
+        let mut game = Game::new(3, Ruleset::chinese());
+        game.set_komi(0.0);
+        for l in &["B2", "C1", "A1", "C2", "A3", "B3", "C3"] {
+            let tomove = game.tomove();
+            game.play(tomove, Move::Play(l.parse().unwrap())).unwrap();
+        }
+
+        // The same stones, but with no history to rule out retaking the ko
+        let mut setup = Game::new(3, Ruleset::chinese());
+        setup.set_komi(0.0);
+        for l in game.board().locations() { setup.setup(l, game.board().get(l)); }
+        setup.set_tomove(White);
+
+        assert_eq!(Solver::new(setup).unwrap().solve().margin(), -9.0);
+
+        let mut s = Solver::new(game).unwrap();
+        assert_eq!(s.solve().margin(), 9.0);
+        // The value depended on the ko, so must not be reused as exact
+        assert!(s.table[&s.key()].depth != usize::MAX);
+    }
+}
+
@@ -0,0 +1,281 @@
+// Recognise tactical patterns by reading out the moves which follow a candidate
+// move on a scratch copy of the board.
+
+use board::Board;
+use group::Group;
+use location::Location;
+use stone::Stone;
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum Tactic {
+    /// The move sacrifices stones which the opponent takes by playing at
+    /// `capture`, only for the capturing group to be retaken at `recapture`
+    Snapback {
+        sacrifice: Vec<Location>,
+        capture: Location,
+        recapture: Location,
+        gain: Vec<Location>,
+    },
+
+    /// A single stone played inside the opponent's shape, which once
+    /// captured at `capture` leaves a false eye at `eye`
+    ThrowIn {
+        stone: Location,
+        capture: Location,
+        eye: Location,
+    },
+}
+
+// Play a move on a copy of the board, returning None if it's not possible or
+// is suicide.
+fn play(board: &Board, loc: Location, colour: Stone) -> Option<Board> {
+    let mut b = board.clone();
+    if b.play(loc, colour) && b.get(loc) == Some(colour) { Some(b) } else { None }
+}
+
+// If the group has a single liberty, return it
+fn atari(board: &Board, group: &Group) -> Option<Location> {
+    let libs: Vec<_> = board.liberties(group);
+    if libs.len() == 1 { Some(libs[0]) } else { None }
+}
+
+/// Is `colour` playing at `loc` a sacrifice which sets up a snapback?
+pub fn snapback(board: &Board, loc: Location, colour: Stone) -> Option<Tactic> {
+    let b1 = match play(board, loc, colour) { Some(b) => b, None => return None };
+    let sacrifice = b1.group_at(loc).expect("missing played stone");
+    let capture = match atari(&b1, &sacrifice) { Some(l) => l, None => return None };
+
+    let b2 = match play(&b1, capture, !colour) { Some(b) => b, None => return None };
+    if b2.get(loc).is_some() { return None }
+
+    let capturer = b2.group_at(capture).expect("missing capturing stone");
+    // A single stone which took a single stone is retaken in a ko, not a
+    // snapback
+    if capturer.len() < 2 && sacrifice.len() < 2 { return None }
+    let recapture = match atari(&b2, &capturer) { Some(l) => l, None => return None };
+
+    let b3 = match play(&b2, recapture, colour) { Some(b) => b, None => return None };
+    let gain: Vec<_> = b2.locations()
+        .filter(|l| b2.get(l) == Some(!colour) && b3.get(l).is_none())
+        .collect();
+
+    Some(Tactic::Snapback {
+        sacrifice: sacrifice.locations().collect(),
+        capture: capture,
+        recapture: recapture,
+        gain: gain,
+    })
+}
+
+/// Is `colour` playing at `loc` a throw-in which makes a false eye?
+pub fn throw_in(board: &Board, loc: Location, colour: Stone) -> Option<Tactic> {
+    let b1 = match play(board, loc, colour) { Some(b) => b, None => return None };
+    let stone = b1.group_at(loc).expect("missing played stone");
+    if stone.len() != 1 { return None }
+    let capture = match atari(&b1, &stone) { Some(l) => l, None => return None };
+
+    // Must be thrown in among the opponent's stones
+    let inside = loc.neighbours()
+        .filter(|n| b1.validloc(n))
+        .all(|n| n == capture || b1.get(n) == Some(!colour));
+    if !inside { return None }
+
+    let b2 = match play(&b1, capture, !colour) { Some(b) => b, None => return None };
+    if b2.get(loc).is_some() { return None }
+
+    if b2.eyelike(loc, !colour) && !b2.true_eye(loc, !colour) {
+        Some(Tactic::ThrowIn { stone: loc, capture: capture, eye: loc })
+    } else {
+        None
+    }
+}
+
+// With the defender to move, does the group at `target` die in a ladder
+// whatever it does? Groups not in atari are taken to have escaped.
+fn ladder_dies(board: &Board, target: Location, depth: usize) -> bool {
+    let group = match board.group_at(target) { Some(g) => g, None => return true };
+    let lib = match atari(board, &group) { Some(l) => l, None => return false };
+    let defender = group.colour();
+
+    // Run, or capture one of the stones around it
+    let mut escapes = vec![lib];
+    for n in group.neighbours().iter() {
+        match board.group_at(n) {
+            Some(ref g) if g.colour() != defender => escapes.extend(atari(board, g)),
+            _ => (),
+        }
+    }
+
+    escapes.into_iter().all(|l| match play(board, l, defender) {
+        Some(b) => ladder_works(&b, target, depth),
+        None => true,
+    })
+}
+
+// With the attacker to move, can the group at `target` be captured by giving
+// atari after atari?
+fn ladder_works(board: &Board, target: Location, depth: usize) -> bool {
+    let group = match board.group_at(target) { Some(g) => g, None => return true };
+    let libs: Vec<_> = board.liberties(&group);
+    match libs.len() {
+        1 => return true,
+        2 if depth > 0 => (),
+        _ => return false,
+    }
+
+    libs.into_iter().any(|l| match play(board, l, !group.colour()) {
+        Some(b) => ladder_dies(&b, target, depth - 1),
+        None => false,
+    })
+}
+
+/// Does `colour` playing at `loc` put an opposing group in atari which then
+/// can't escape a ladder?
+pub fn ladder_capture(board: &Board, loc: Location, colour: Stone) -> bool {
+    let b = match play(board, loc, colour) { Some(b) => b, None => return false };
+    let depth = board.size() * board.size();
+
+    loc.neighbours()
+        .filter(|&n| b.get(n) == Some(!colour))
+        .any(|n| ladder_dies(&b, n, depth))
+}
+
+/// Does `colour` playing at `loc` rescue one of its groups from atari, in a
+/// way which can't then be caught in a ladder?
+pub fn ladder_escape(board: &Board, loc: Location, colour: Stone) -> bool {
+    let depth = board.size() * board.size();
+    let rescued: Vec<_> = loc.neighbours()
+        .filter(|&n| board.get(n) == Some(colour))
+        .filter(|&n| board.group_at(n).map_or(false, |g| atari(board, &g) == Some(loc)))
+        .collect();
+    if rescued.is_empty() { return false }
+
+    let b = match play(board, loc, colour) { Some(b) => b, None => return false };
+    !ladder_works(&b, loc, depth)
+}
+
+/// All the tactical patterns `colour` playing at `loc` sets up
+pub fn tactics(board: &Board, loc: Location, colour: Stone) -> Vec<Tactic> {
+    snapback(board, loc, colour).into_iter()
+        .chain(throw_in(board, loc, colour))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Tactic, snapback, throw_in, tactics, ladder_capture, ladder_escape};
+    use board::Board;
+    use location::Location;
+    use stone::Stone::{Black, White};
+
+    fn loc(s: &str) -> Location { Location::from_str(s).unwrap() }
+
+    #[test] fn snap() {
+        let b = Board::from_str("\
+. . . . .
+. . . . .
+# # # . .
+O O O # .
+. . # . .
+").expect("board");
+
+        assert_eq!(snapback(&b, loc("A1"), Black),
+                   Some(Tactic::Snapback {
+                       sacrifice: vec![loc("A1")],
+                       capture: loc("B1"),
+                       recapture: loc("A1"),
+                       gain: vec![loc("A2"), loc("B1"), loc("B2"), loc("C2")],
+                   }));
+
+        // B1 ataris White directly, but joins C1 with two liberties, so it
+        // offers no stone for White to take and nothing snaps back
+        assert_eq!(snapback(&b, loc("B1"), Black), None);
+        assert_eq!(snapback(&b, loc("D4"), Black), None);
+    }
+
+    #[test] fn ko_is_not_snapback() {
+        let b = Board::from_str("\
+. # O .
+# . # O
+. # O .
+. . . .
+").expect("board");
+
+        assert_eq!(snapback(&b, loc("B3"), White), None);
+    }
+
+    #[test] fn one_stone_takes_two() {
+        let b = Board::from_str("\
+. . . . .
+. . . . .
+. . . . .
+O O # . .
+. # . # .
+").expect("board");
+
+        assert_eq!(snapback(&b, loc("A1"), Black),
+                   Some(Tactic::Snapback {
+                       sacrifice: vec![loc("A1"), loc("B1")],
+                       capture: loc("C1"),
+                       recapture: loc("B1"),
+                       gain: vec![loc("C1")],
+                   }));
+    }
+
+    #[test] fn throw() {
+        let b = Board::from_str("\
+. . . . .
+. . . . .
+. . . . .
+. # . O .
+. O . O .
+").expect("board");
+
+        assert_eq!(throw_in(&b, loc("C1"), Black),
+                   Some(Tactic::ThrowIn { stone: loc("C1"), capture: loc("C2"), eye: loc("C1") }));
+        assert_eq!(tactics(&b, loc("C1"), Black).len(), 1);
+
+        // Without the Black stone on the diagonal the eye is real
+        let b = Board::from_str("\
+. . . . .
+. . . . .
+. . . . .
+. . . O .
+. O . O .
+").expect("board");
+        assert_eq!(throw_in(&b, loc("C1"), Black), None);
+    }
+
+    #[test] fn ladder() {
+        let pos = "\
+. . . . . . . . .
+. . . . . . . . .
+. . . . . . O . .
+. . . . # . . . .
+. . . # O . . . .
+. . . . . # . . .
+. . . . . . . . .
+. . . . . . . . .
+. . . . . . . . .
+";
+        let b = Board::from_str(pos).expect("board");
+        assert!(ladder_capture(&b, loc("F5"), Black));
+        // Atari from the other side drives White towards its stone at G7
+        assert!(!ladder_capture(&b, loc("E4"), Black));
+
+        // Once in atari, running doesn't help
+        let mut b2 = b.clone();
+        let _ = b2.play(loc("F5"), Black);
+        assert!(!ladder_escape(&b2, loc("E4"), White));
+
+        // unless there's a White stone in the way
+        let mut b3 = b2.clone();
+        let _ = b3.add(loc("B2"), White);
+        assert!(ladder_escape(&b3, loc("E4"), White));
+        let mut b4 = b.clone();
+        let _ = b4.add(loc("B2"), White);
+        assert!(!ladder_capture(&b4, loc("F5"), Black));
+    }
+}
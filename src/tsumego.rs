@@ -0,0 +1,409 @@
+// Solve local life and death problems by search.
+//
+// The attacker wins by capturing the target group and the defender by making
+// it unconditionally alive, or by getting to the end (both sides passing)
+// with the target still on the board, which covers seki. Moves are only
+// considered inside the problem's region.
+//
+// Kos are handled by searching twice. Repeating a position along the current
+// line is forbidden, except for the side which is given every ko; if the
+// attacker wins even when the defender wins every ko the group is dead, if
+// the defender wins even when the attacker does it lives, and otherwise the
+// result is ko.
+//
+// Results which were cut short by the depth limit are kept in the
+// transposition table along with how deep they were searched, and only used
+// again for positions which have no more than that left to search. Results
+// which depended on a repetition being forbidden depend on the line which
+// led to them, so they're only kept for following the best line afterwards.
+
+use std::collections::hash_map::HashMap;
+use std::usize;
+
+use board::{Board, PointSet};
+use eyeshape;
+use game::{self, Game, Move};
+use group::Group;
+use location::Location;
+use safety;
+use sgf::{self, Node};
+use stone::Stone;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum Outcome {
+    /// The target can be captured, whoever wins kos
+    Kill,
+    /// The target lives (perhaps in seki), whoever wins kos
+    Live,
+    /// The result depends on who wins a ko
+    Ko,
+}
+
+#[derive(Debug, Clone)]
+pub struct Solution {
+    outcome: Outcome,
+    pv: Vec<(Stone, Move)>,
+}
+
+impl Solution {
+    pub fn outcome(&self) -> Outcome { self.outcome }
+
+    /// Best play for both sides, starting with the side to move
+    pub fn pv(&self) -> &[(Stone, Move)] { &self.pv }
+}
+
+#[derive(Debug, Clone)]
+pub struct Problem {
+    board: Board,
+    target: Location,
+    region: PointSet,
+    tomove: Stone,
+    depth: usize,
+}
+
+impl Problem {
+    /// Problem of whether `target` lives with `tomove` to play, considering
+    /// moves at the points in `region`
+    pub fn new(board: Board, target: &Group, region: PointSet, tomove: Stone) -> Problem {
+        let loc = target.locations().next().expect("empty target group");
+        assert_eq!(board.get(loc), Some(target.colour()));
+
+        Problem {
+            depth: 2 * region.len() + 4,
+            board: board,
+            target: loc,
+            region: region,
+            tomove: tomove,
+        }
+    }
+
+    /// Set up a problem from an SGF root node, with the stones from `AB`/`AW`
+    /// and the side to move from `PL` (default Black).
+    ///
+    /// The target is a stone marked with `TR`, `SQ`, `MA` or `CR`, or if there
+    /// isn't one the biggest group of the side not to move. The region is the
+    /// empty points inside the area around all the stones.
+    pub fn from_sgf(root: &Node) -> sgf::Result<Problem> {
+        let game = try!(Game::from_sgf(root));
+        let board = game.board().clone();
+        let tomove = game.tomove();
+
+        let mut marked = None;
+        for id in &["TR", "SQ", "MA", "CR"] {
+            if let Some(p) = root.prop(id) {
+                for v in try!(p.values()) {
+                    let loc = v.gomove().and_then(|pt| game::sgf_location(pt, board.size()));
+                    if let Some(loc) = loc {
+                        if marked.is_none() && board.get(loc).is_some() { marked = Some(loc) }
+                    }
+                }
+            }
+        }
+
+        let target = match marked {
+            Some(loc) => board.group_at(loc),
+            None => board.groups::<Vec<_>>(!tomove).into_iter().max_by_key(|g| g.len()),
+        };
+        let target = match target { Some(t) => t, None => return Err(sgf::Error::ValueError) };
+
+        let stones: Vec<_> = board.locations().filter(|l| board.get(l).is_some()).collect();
+        let lo = |f: fn(&Location) -> usize| stones.iter().map(f).min().unwrap_or(0).saturating_sub(1);
+        let hi = |f: fn(&Location) -> usize| stones.iter().map(f).max().unwrap_or(0) + 1;
+        let (c0, c1, r0, r1) = (lo(Location::col), hi(Location::col), lo(Location::row), hi(Location::row));
+        let region = board.locations()
+            .filter(|l| board.get(l).is_none())
+            .filter(|l| c0 <= l.col() && l.col() <= c1 && r0 <= l.row() && l.row() <= r1)
+            .collect();
+
+        Ok(Problem::new(board, &target, region, tomove))
+    }
+
+    pub fn board(&self) -> &Board { &self.board }
+    pub fn target(&self) -> Group { self.board.group_at(self.target).expect("target missing") }
+    pub fn region(&self) -> &PointSet { &self.region }
+    pub fn tomove(&self) -> Stone { self.tomove }
+
+    pub fn defender(&self) -> Stone { self.board.get(self.target).expect("target missing") }
+    pub fn attacker(&self) -> Stone { !self.defender() }
+
+    /// Limit on how many moves deep to search; when it runs out the target
+    /// is treated as living
+    pub fn set_depth(&mut self, depth: usize) { self.depth = depth }
+
+    pub fn solve(&self) -> Solution {
+        let attacker = self.attacker();
+
+        // Defender wins every ko
+        let mut kill = Search::new(self, self.defender());
+        let killed = kill.run() == (self.tomove == attacker);
+        if killed {
+            return Solution { outcome: Outcome::Kill, pv: kill.pv() }
+        }
+
+        // Attacker wins every ko
+        let mut live = Search::new(self, attacker);
+        let lives = live.run() != (self.tomove == attacker);
+        if lives {
+            return Solution { outcome: Outcome::Live, pv: live.pv() }
+        }
+
+        let pv = if self.tomove == attacker { live.pv() } else { kill.pv() };
+        Solution { outcome: Outcome::Ko, pv: pv }
+    }
+}
+
+struct Search<'a> {
+    problem: &'a Problem,
+    defender: Stone,
+    kowinner: Stone,
+    table: HashMap<(u64, Stone, bool), Entry>,
+    path: Vec<u64>,
+}
+
+// The result of searching a position: whether the side to move wins, the
+// move which does it (or the first tried if nothing does), how deep it was
+// searched if the depth limit cut it short (usize::MAX if not), and whether
+// it depended on repetitions being forbidden along the current line
+#[derive(Debug, Copy, Clone)]
+struct Entry {
+    win: bool,
+    best: Move,
+    depth: usize,
+    ko: bool,
+}
+
+impl Entry {
+    fn exact(win: bool) -> Entry {
+        Entry { win: win, best: Move::Pass, depth: usize::MAX, ko: false }
+    }
+}
+
+impl<'a> Search<'a> {
+    fn new(problem: &'a Problem, kowinner: Stone) -> Search<'a> {
+        Search {
+            problem: problem,
+            defender: problem.defender(),
+            kowinner: kowinner,
+            table: HashMap::new(),
+            path: vec![problem.board.zobrist()],
+        }
+    }
+
+    // Does the side to move win the problem?
+    fn run(&mut self) -> bool {
+        let p = self.problem;
+        self.search(&p.board, p.tomove, false, p.depth).win
+    }
+
+    fn alive(&self, board: &Board) -> bool {
+        safety::unconditional(board, self.defender).iter().any(|g| g.contains(self.problem.target))
+    }
+
+    // Candidate moves, most promising first: vital points of the defender's
+    // eye spaces, then the target's liberties, then the rest of the region,
+    // then passing. Nobody fills in their own real eye.
+    fn moves(&self, board: &Board, tomove: Stone) -> Vec<Move> {
+        let target = board.group_at(self.problem.target).expect("target missing");
+        let libs: PointSet = board.liberties(&target);
+
+        let mut first = Vec::new();
+        let mut second = Vec::new();
+        let mut rest = Vec::new();
+        let vital: Vec<_> = eyeshape::eye_spaces(board, self.defender).iter()
+            .flat_map(|e| e.vital().to_vec())
+            .collect();
+
+        for l in self.problem.region.iter() {
+            if board.get(l).is_some() { continue }
+            if board.eyelike(l, tomove) && board.true_eye(l, tomove) { continue }
+
+            if vital.contains(&l) {
+                first.push(Move::Play(l))
+            } else if libs.contains(l) {
+                second.push(Move::Play(l))
+            } else {
+                rest.push(Move::Play(l))
+            }
+        }
+
+        first.extend(second);
+        first.extend(rest);
+        first.push(Move::Pass);
+        first
+    }
+
+    fn search(&mut self, board: &Board, tomove: Stone, passed: bool, depth: usize) -> Entry {
+        let defender = self.defender;
+
+        if board.get(self.problem.target) != Some(defender) { return Entry::exact(tomove != defender) }
+        if depth == 0 { return Entry { win: tomove == defender, best: Move::Pass, depth: 0, ko: false } }
+
+        let key = (board.zobrist(), tomove, passed);
+        if let Some(&e) = self.table.get(&key) {
+            if !e.ko && e.depth >= depth { return e }
+        }
+
+        if self.alive(board) { return Entry::exact(tomove == defender) }
+
+        let mut win = false;
+        let mut best = None;
+        let mut limited = false;
+        let mut ko = false;
+
+        for mv in self.moves(board, tomove) {
+            let next = match mv {
+                Move::Pass if passed => {
+                    // Both sides passed, so the target survives
+                    if tomove == defender {
+                        win = true;
+                        best = Some(mv);
+                        limited = false;
+                        ko = false;
+                        break
+                    }
+                    if best.is_none() { best = Some(mv) }
+                    continue
+                },
+                Move::Pass => board.clone(),
+                Move::Play(loc) => {
+                    let mut b = board.clone();
+                    if !b.play(loc, tomove) || b.get(loc) != Some(tomove) { continue }
+                    if tomove != self.kowinner && self.path.contains(&b.zobrist()) {
+                        ko = true;
+                        continue
+                    }
+                    b
+                },
+            };
+
+            if best.is_none() { best = Some(mv) }
+
+            self.path.push(next.zobrist());
+            let e = self.search(&next, !tomove, mv == Move::Pass, depth - 1);
+            let _ = self.path.pop();
+
+            // A win only depends on the line which wins, but a loss on
+            // every line
+            if !e.win {
+                win = true;
+                best = Some(mv);
+                limited = e.depth != usize::MAX;
+                ko = e.ko;
+                break
+            }
+            limited |= e.depth != usize::MAX;
+            ko |= e.ko;
+        }
+
+        let entry = Entry {
+            win: win,
+            best: best.unwrap_or(Move::Pass),
+            depth: if limited { depth } else { usize::MAX },
+            ko: ko,
+        };
+        let _ = self.table.insert(key, entry);
+        entry
+    }
+
+    // Follow the best moves found by the search
+    fn pv(&self) -> Vec<(Stone, Move)> {
+        let mut board = self.problem.board.clone();
+        let mut tomove = self.problem.tomove;
+        let mut passed = false;
+        let mut pv = Vec::new();
+        let mut seen = vec![board.zobrist()];
+
+        while pv.len() < self.problem.depth && board.get(self.problem.target) == Some(self.defender) {
+            let mv = match self.table.get(&(board.zobrist(), tomove, passed)) {
+                Some(e) => e.best,
+                None => break,
+            };
+            pv.push((tomove, mv));
+
+            match mv {
+                Move::Pass if passed => break,
+                Move::Pass => passed = true,
+                Move::Play(loc) => {
+                    let _ = board.play(loc, tomove);
+                    passed = false;
+
+                    // Stop once a ko starts going round
+                    if seen.contains(&board.zobrist()) { break }
+                    seen.push(board.zobrist());
+                },
+            }
+            tomove = !tomove;
+        }
+
+        pv
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use super::{Problem, Outcome};
+    use board::{Board, PointSet};
+    use game::Move;
+    use location::Location;
+    use sgf;
+    use stone::Stone::{Black, White};
+
+    fn loc(s: &str) -> Location { Location::from_str(s).unwrap() }
+
+    #[test] fn straight_three() {
+        let b = Board::from_str("\
+. . . . . . .
+. . . . . . .
+. . . . . . .
+. . . . . . .
+O O O O O . .
+# # # # O . .
+. . . # O . .
+").expect("board");
+        let target = b.group_at(loc("A2")).unwrap();
+        let region: PointSet = vec![loc("A1"), loc("B1"), loc("C1")].into_iter().collect();
+
+        let p = Problem::new(b.clone(), &target, region.clone(), White);
+        let s = p.solve();
+        assert_eq!(s.outcome(), Outcome::Kill);
+        assert_eq!(s.pv()[0], (White, Move::Play(loc("B1"))));
+
+        let p = Problem::new(b, &target, region, Black);
+        let s = p.solve();
+        assert_eq!(s.outcome(), Outcome::Live);
+        assert_eq!(s.pv()[0], (Black, Move::Play(loc("B1"))));
+    }
+
+    #[test] fn ko() {
+        let b = Board::from_str("\
+. . O O .
+# O O O O
+# # # O .
+# # # O O
+# . # O O
+").expect("board");
+        let target = b.group_at(loc("A1")).unwrap();
+        let region: PointSet = b.locations().filter(|l| b.get(l).is_none()).collect();
+
+        let p = Problem::new(b.clone(), &target, region.clone(), Black);
+        let s = p.solve();
+        assert_eq!(s.outcome(), Outcome::Ko);
+        assert_eq!(&s.pv()[..2], &[(Black, Move::Play(loc("B5"))), (White, Move::Play(loc("A5")))]);
+
+        let p = Problem::new(b, &target, region, White);
+        assert_eq!(p.solve().outcome(), Outcome::Kill);
+    }
+
+    #[test] fn from_sgf() {
+        let sgf = b"(;GM[1]SZ[7]AB[af][bf][cf][df][dg]AW[ae][be][ce][de][ee][ef][eg]PL[W])";
+        let root = &sgf::parser(sgf).expect("parse")[0];
+        let p = Problem::from_sgf(root).expect("problem");
+
+        assert_eq!(p.tomove(), White);
+        assert_eq!(p.defender(), Black);
+        assert_eq!(p.target().len(), 5);
+        assert_eq!(p.solve().outcome(), Outcome::Kill);
+    }
+}